@@ -0,0 +1,258 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::*;
+
+use directories::ProjectDirs;
+use json::JsonValue;
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How many of the most recently fetched entries are kept on disk.
+const MAX_SAVED_ENTRIES: usize = 500;
+
+/// What a cached response is for: the source's adapter and formatted URL, and the search term.
+pub type CacheKey = (String, String);
+
+struct CacheEntry {
+    /// Unix timestamp (in seconds) of when the suggestions were fetched.
+    fetched: u64,
+    suggestions: Suggestions,
+}
+
+/// Suggestions from network sources by what they were fetched for, so that going back to an
+/// earlier search term doesn't need another request, and a failed request can fall back to an
+/// older response. Optionally kept on disk as `suggestions.json` in the cache dir.
+#[derive(Default)]
+pub struct SuggestionCache {
+    path: Option<PathBuf>,
+    /// How many seconds a response is used for instead of asking again.
+    ttl: u64,
+    entries: HashMap<CacheKey, CacheEntry>,
+}
+
+impl SuggestionCache {
+    /// A cache that only lives as long as the process.
+    pub fn new(ttl: u64) -> SuggestionCache {
+        SuggestionCache {
+            ttl,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the cache from `suggestions.json` in the cache dir. Starts out empty if it doesn't
+    /// exist or can't be parsed, in which case it's overwritten on `save`.
+    pub fn load(ttl: u64) -> io::Result<SuggestionCache> {
+        let proj_dirs = ProjectDirs::from("com", "efskap", "sefr")
+            .ok_or_else(|| io::Error::other("Couldn't get cache dir."))?;
+        SuggestionCache::load_from(&proj_dirs.cache_dir().join("suggestions.json"), ttl)
+    }
+
+    pub fn load_from(path: &Path, ttl: u64) -> io::Result<SuggestionCache> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let mut cache = SuggestionCache::new(ttl);
+        cache.path = Some(path.to_path_buf());
+        if let Ok(data) = json::parse(&text) {
+            cache.entries = data.members().filter_map(parse_entry).collect();
+        }
+        Ok(cache)
+    }
+
+    /// Writes the most recent entries back to the file the cache was loaded from. Does nothing
+    /// for a cache that wasn't loaded from a file.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|x| Reverse(x.1.fetched));
+        let mut data = JsonValue::new_array();
+        for ((source, term), entry) in entries.into_iter().take(MAX_SAVED_ENTRIES) {
+            let suggs = &entry.suggestions;
+            let _ = data.push(json::object! {
+                "source" => source.as_str(),
+                "term" => term.as_str(),
+                "fetched" => entry.fetched,
+                "suggestions" => suggs.sugg_terms.clone(),
+                "descriptions" => suggs.descriptions.clone(),
+                "urls" => suggs.urls.clone(),
+            });
+        }
+        write_atomically(path, &data.dump())
+    }
+
+    /// The cached suggestions for `key`, unless they're older than the TTL.
+    pub fn get(&self, key: &CacheKey) -> Option<&Suggestions> {
+        self.get_at(key, unix_now())
+    }
+
+    fn get_at(&self, key: &CacheKey, now: u64) -> Option<&Suggestions> {
+        self.entries
+            .get(key)
+            .filter(|x| now.saturating_sub(x.fetched) < self.ttl)
+            .map(|x| &x.suggestions)
+    }
+
+    /// The cached suggestions for `key`, however old they are.
+    pub fn get_stale(&self, key: &CacheKey) -> Option<&Suggestions> {
+        self.entries.get(key).map(|x| &x.suggestions)
+    }
+
+    pub fn insert(&mut self, key: CacheKey, suggestions: Suggestions) {
+        self.insert_at(key, suggestions, unix_now());
+    }
+
+    fn insert_at(&mut self, key: CacheKey, suggestions: Suggestions, fetched: u64) {
+        self.entries.insert(
+            key,
+            CacheEntry {
+                fetched,
+                suggestions,
+            },
+        );
+    }
+}
+
+fn parse_entry(value: &JsonValue) -> Option<(CacheKey, CacheEntry)> {
+    let strings = |key: &str| -> Option<Vec<String>> {
+        value[key]
+            .members()
+            .map(|x| x.as_str().map(str::to_string))
+            .collect()
+    };
+    let term = value["term"].as_str()?.to_string();
+    Some((
+        (value["source"].as_str()?.to_string(), term.clone()),
+        CacheEntry {
+            fetched: value["fetched"].as_u64()?,
+            suggestions: Suggestions {
+                term,
+                sugg_terms: strings("suggestions")?,
+                descriptions: strings("descriptions")?,
+                urls: strings("urls")?,
+                ..Default::default()
+            },
+        },
+    ))
+}
+
+impl SuggestionSource {
+    /// What `engine`'s responses from this source are cached under, or `None` for sources that
+    /// don't go over the network and so aren't worth caching.
+    pub fn cache_key(&self, engine: &Engine, search_term: &str) -> Option<CacheKey> {
+        match self.adapter {
+            SourceKind::Adapter(ref name) if name.uses_url() => Some((
+                format!("{}\t{}", name, engine.format_url(&self.url, search_term)),
+                search_term.to_string(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Like `fetch_from_source`, but answers from `cache` while the response there is fresh, and
+/// falls back to an expired one if the request fails.
+pub fn fetch_cached(
+    cache: &Mutex<SuggestionCache>,
+    engine: &Engine,
+    prefix: &str,
+    source: &SuggestionSource,
+    history: Option<&History>,
+    search_term: &str,
+) -> Result<Suggestions, SuggestionError> {
+    let key = match source.cache_key(engine, search_term) {
+        Some(key) => key,
+        None => return fetch_from_source(engine, prefix, source, history, search_term),
+    };
+    if let Some(suggs) = cache.lock().unwrap().get(&key) {
+        return Ok(suggs.clone());
+    }
+    match fetch_from_source(engine, prefix, source, history, search_term) {
+        Ok(suggs) => {
+            cache.lock().unwrap().insert(key, suggs.clone());
+            Ok(suggs)
+        }
+        Err(e) => cache.lock().unwrap().get_stale(&key).cloned().ok_or(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggs(terms: &[&str]) -> Suggestions {
+        Suggestions {
+            term: "ru".into(),
+            sugg_terms: terms.iter().map(|x| x.to_string()).collect(),
+            urls: vec![String::new(), "https://www.rust-lang.org".into()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ttl() {
+        let key: CacheKey = ("opensearch\thttps://example.com/?q=ru".into(), "ru".into());
+        let mut cache = SuggestionCache::new(60);
+        cache.insert_at(key.clone(), suggs(&["ruby", "rust"]), 1000);
+        assert_eq!(
+            cache.get_at(&key, 1059).unwrap().sugg_terms,
+            vec!["ruby", "rust"]
+        );
+        assert!(cache.get_at(&key, 1060).is_none());
+        assert!(cache.get_stale(&key).is_some());
+        assert!(cache.get_at(&("other".into(), "ru".into()), 1000).is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("sefr-cache-{}.json", std::process::id()));
+        let key: CacheKey = ("opensearch\thttps://example.com/?q=ru".into(), "ru".into());
+        let mut cache = SuggestionCache::load_from(&path, 60).unwrap();
+        cache.insert(key.clone(), suggs(&["ruby", "rust"]));
+        cache.save().unwrap();
+
+        let loaded = SuggestionCache::load_from(&path, 60).unwrap();
+        fs::remove_file(&path).unwrap();
+        let suggs = loaded.get(&key).unwrap();
+        assert_eq!(suggs.term, "ru");
+        assert_eq!(suggs.sugg_terms, vec!["ruby", "rust"]);
+        assert_eq!(suggs.url(1), Some("https://www.rust-lang.org"));
+    }
+
+    #[test]
+    fn broken_file_is_replaced() {
+        let path = std::env::temp_dir().join(format!("sefr-broken-{}.json", std::process::id()));
+        fs::write(&path, "[{\"source\": ").unwrap();
+        let key: CacheKey = ("opensearch\thttps://example.com/?q=ru".into(), "ru".into());
+        let mut cache = SuggestionCache::load_from(&path, 60).unwrap();
+        assert!(cache.get_stale(&key).is_none());
+        cache.insert(key.clone(), suggs(&["rust"]));
+        cache.save().unwrap();
+
+        let loaded = SuggestionCache::load_from(&path, 60).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get(&key).unwrap().sugg_terms, vec!["rust"]);
+    }
+}
@@ -0,0 +1,181 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: sefr [OPTIONS] [QUERY]...
+
+Interactively open search engines / parametric URLs.
+
+Arguments:
+  [QUERY]...             Initial contents of the input buffer, e.g. `sefr yt lofi`
+
+Options:
+  -p, --print-url        Print the search URL for QUERY instead of opening it, then exit
+  -s, --suggest          Print suggestions for QUERY, one per line, then exit
+      --json             With --suggest, print the suggestions as a JSON object instead
+  -e, --engine <PREFIX>  Lock the engine to the one with this prefix
+                         (use `_default` for the default engine)
+  -c, --config <PATH>    Load the config from PATH instead of the default location
+  -h, --help             Print this help and exit
+  -V, --version          Print the version and exit";
+
+/// What to do with the query once the config is loaded.
+#[derive(Debug, Default, PartialEq)]
+pub enum Mode {
+    /// Start the interactive UI with the query in the input buffer.
+    #[default]
+    Interactive,
+    /// Resolve the query to a search URL and print it without touching the terminal.
+    PrintUrl,
+    /// Fetch suggestions for the query and print them without touching the terminal.
+    Suggest,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub mode: Mode,
+    pub json: bool,
+    pub initial_input: String,
+    pub engine: Option<String>,
+    pub config_path: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Args),
+    Help,
+    Version,
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+pub fn version() -> String {
+    format!("sefr {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Parses the arguments (without the program name) into a `Command`.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut parsed = Args::default();
+    let mut query: Vec<String> = Vec::new();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            // everything after this is part of the query, even if it looks like a flag
+            query.extend(&mut args);
+            break;
+        }
+        // support both `--engine yt` and `--engine=yt`
+        let (flag, inline_value) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (&arg[..i], Some(arg[i + 1..].to_string())),
+            _ => (arg.as_str(), None),
+        };
+        let mut value = |name: &str| {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or(format!("Option '{}' requires a value.", name))
+        };
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-p" | "--print-url" => parsed.mode = Mode::PrintUrl,
+            "-s" | "--suggest" => parsed.mode = Mode::Suggest,
+            "--json" => parsed.json = true,
+            "-e" | "--engine" => parsed.engine = Some(value(flag)?),
+            "-c" | "--config" => parsed.config_path = Some(PathBuf::from(value(flag)?)),
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unrecognized option '{}'.", flag));
+            }
+            _ => query.push(arg),
+        }
+    }
+    parsed.initial_input = query.join(" ");
+    Ok(Command::Run(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|x| x.to_string()))
+    }
+
+    #[test]
+    fn query_and_options() {
+        assert_eq!(
+            parse(&["yt", "lofi", "-e", "yt", "--config=/tmp/sefr.toml"]),
+            Ok(Command::Run(Args {
+                mode: Mode::Interactive,
+                json: false,
+                initial_input: "yt lofi".into(),
+                engine: Some("yt".into()),
+                config_path: Some("/tmp/sefr.toml".into()),
+            }))
+        );
+        assert_eq!(parse(&[]), Ok(Command::Run(Args::default())));
+    }
+
+    #[test]
+    fn print_url_mode() {
+        assert_eq!(
+            parse(&["--print-url", "w rust ownership"]),
+            Ok(Command::Run(Args {
+                mode: Mode::PrintUrl,
+                initial_input: "w rust ownership".into(),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn suggest_mode() {
+        assert_eq!(
+            parse(&["--suggest", "--json", "yt never gonna"]),
+            Ok(Command::Run(Args {
+                mode: Mode::Suggest,
+                json: true,
+                initial_input: "yt never gonna".into(),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn flags_after_double_dash_are_query() {
+        assert_eq!(
+            parse(&["--", "-h", "--engine"]),
+            Ok(Command::Run(Args {
+                initial_input: "-h --engine".into(),
+                ..Default::default()
+            }))
+        );
+    }
+
+    #[test]
+    fn help_version_and_errors() {
+        assert_eq!(parse(&["foo", "--help"]), Ok(Command::Help));
+        assert_eq!(parse(&["-V"]), Ok(Command::Version));
+        assert!(parse(&["--engine"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
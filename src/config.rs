@@ -17,6 +17,7 @@
 
 use crate::*;
 
+use crate::json_path::find_unquoted;
 use directories::ProjectDirs;
 use serde::de::SeqAccess;
 use serde::ser::SerializeSeq;
@@ -25,6 +26,7 @@ use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 const DEFAULT_NAME: &str = "%%DEFAULT%%";
@@ -173,25 +175,198 @@ impl Hash for KeyBind {
     }
 }
 
+/// The whole config file. Once validated, the default engine is stored under the empty prefix.
 #[derive(Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub open_with: OpenWith,
+    /// Whether to remember submitted queries and suggest them again.
+    #[serde(default = "_true")]
+    pub history: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub suggestions: SuggestionSettings,
+    /// How suggestions are requested, for engines that don't say otherwise.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub http: HttpSettings,
     pub engines: HashMap<String, Engine>,
     pub keybinds: HashMap<KeyBind, BindableAction>,
 }
+impl Config {
+    /// The `open_with` setting for `engine`, falling back to the global one.
+    pub fn open_with_for<'a>(&'a self, engine: &'a Engine) -> &'a OpenWith {
+        engine.open_with.as_ref().unwrap_or(&self.open_with)
+    }
+}
+
+/// How suggestions are fetched while typing, as set in the `[suggestions]` table.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SuggestionSettings {
+    /// How long to wait after the last keystroke before asking any sources that aren't history.
+    #[serde(default = "_default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// How many requests can be in flight at once.
+    #[serde(default = "_default_max_workers")]
+    pub max_workers: usize,
+    /// How many seconds a response is reused for before asking the source again.
+    #[serde(default = "_default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// Whether to keep responses in the cache dir between runs, e.g. for offline use.
+    #[serde(default)]
+    pub disk_cache: bool,
+}
+impl Default for SuggestionSettings {
+    fn default() -> Self {
+        SuggestionSettings {
+            debounce_ms: _default_debounce_ms(),
+            max_workers: _default_max_workers(),
+            cache_ttl_secs: _default_cache_ttl_secs(),
+            disk_cache: false,
+        }
+    }
+}
+fn _default_debounce_ms() -> u64 {
+    150
+}
+fn _default_max_workers() -> usize {
+    4
+}
+fn _default_cache_ttl_secs() -> u64 {
+    300
+}
+
+/// What to do with the URL when a search is submitted.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum OpenWith {
+    /// Open it in the system's default browser.
+    #[default]
+    Browser,
+    /// Start a command, with `{url}` in it replaced by the URL (or the URL appended if absent),
+    /// without waiting for it to exit.
+    Command(String),
+    /// Like `Command`, but run in the terminal and wait for it, for programs like `w3m`.
+    Terminal(String),
+    /// Print it to stdout.
+    Print,
+    /// Pipe it into the stdin of a clipboard command, e.g. `xclip -selection clipboard`.
+    Clipboard(String),
+}
+impl fmt::Display for OpenWith {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenWith::Browser => fmt.write_str("browser"),
+            OpenWith::Command(x) => write!(fmt, "command:{}", x),
+            OpenWith::Terminal(x) => write!(fmt, "terminal:{}", x),
+            OpenWith::Print => fmt.write_str("print"),
+            OpenWith::Clipboard(x) => write!(fmt, "clipboard:{}", x),
+        }
+    }
+}
+impl FromStr for OpenWith {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        if lower == "browser" {
+            return Ok(OpenWith::Browser);
+        }
+        if lower == "print" {
+            return Ok(OpenWith::Print);
+        }
+        if lower.starts_with("command:") {
+            let command = &s["command:".len()..];
+            check_command(command)?;
+            return Ok(OpenWith::Command(command.into()));
+        }
+        if lower.starts_with("terminal:") {
+            let command = &s["terminal:".len()..];
+            check_command(command)?;
+            return Ok(OpenWith::Terminal(command.into()));
+        }
+        if lower.starts_with("clipboard:") {
+            let command = &s["clipboard:".len()..];
+            check_command(command)?;
+            return Ok(OpenWith::Clipboard(command.into()));
+        }
+        Err(Self::Err::new(&format!(
+            "Unrecognized open_with string (expected browser, print, command:<cmd>, terminal:<cmd> or clipboard:<cmd>): {}",
+            s
+        )))
+    }
+}
+fn check_command(command: &str) -> Result<(), ConfigError> {
+    match shell_words::split(command) {
+        Ok(ref words) if words.is_empty() => Err(ConfigError::new("Command can't be empty.")),
+        Ok(_) => Ok(()),
+        Err(e) => Err(ConfigError::new(&format!(
+            "Could not parse command '{}': {}",
+            command, e
+        ))),
+    }
+}
+impl<'de> Deserialize<'de> for OpenWith {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
+impl Serialize for OpenWith {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
 
 #[derive(Clone, PartialEq)]
 pub enum SuggestionAdapterName {
     OpenSearch,
-    JsonPath(String),
+    /// `json:<path>` or `json:<path>|<description path>`
+    JsonPath {
+        path: JsonPath,
+        description: Option<JsonPath>,
+    },
+    /// `xml:<element path>`
+    Xml(XmlPath),
+    /// `text` or `text:<regex>`
+    Text(Option<LinePattern>),
+    /// `exec:<command>`
+    Exec(String),
+    /// `file:<path>`
+    File(String),
+    /// Anything registered with `register_adapter`.
+    Custom(CustomAdapter),
+}
+
+impl SuggestionAdapterName {
+    /// Whether the adapter fetches the engine's `suggestion_url`.
+    pub fn uses_url(&self) -> bool {
+        match self {
+            SuggestionAdapterName::Exec(_) | SuggestionAdapterName::File(_) => false,
+            SuggestionAdapterName::Custom(custom) => custom.uses_url(),
+            _ => true,
+        }
+    }
 }
 impl fmt::Display for SuggestionAdapterName {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
             SuggestionAdapterName::OpenSearch => fmt.write_str("opensearch")?,
-            SuggestionAdapterName::JsonPath(x) => {
-                fmt.write_str("json:")?;
-                fmt.write_str(x)?
+            SuggestionAdapterName::JsonPath { path, description } => {
+                write!(fmt, "json:{}", path)?;
+                if let Some(description) = description {
+                    write!(fmt, "|{}", description)?;
+                }
             }
+            SuggestionAdapterName::Xml(path) => write!(fmt, "xml:{}", path)?,
+            SuggestionAdapterName::Text(None) => fmt.write_str("text")?,
+            SuggestionAdapterName::Text(Some(pattern)) => write!(fmt, "text:{}", pattern)?,
+            SuggestionAdapterName::Exec(command) => write!(fmt, "exec:{}", command)?,
+            SuggestionAdapterName::File(path) => write!(fmt, "file:{}", path)?,
+            SuggestionAdapterName::Custom(custom) => write!(fmt, "{}", custom)?,
         };
         Ok(())
     }
@@ -205,8 +380,52 @@ impl FromStr for SuggestionAdapterName {
             return Ok(SuggestionAdapterName::OpenSearch);
         };
         if lower.starts_with("json:") {
-            let jsonpath = &s["json:".len()..];
-            return Ok(SuggestionAdapterName::JsonPath(jsonpath.into()));
+            let paths = &s["json:".len()..];
+            // a `|` inside a quoted field is part of the path
+            let (path, description) = match find_unquoted(paths, '|') {
+                Some(i) => (&paths[..i], Some(&paths[i + 1..])),
+                None => (paths, None),
+            };
+            let parse = |x: &str| {
+                x.trim()
+                    .parse::<JsonPath>()
+                    .map_err(|e| ConfigError::new(&e))
+            };
+            return Ok(SuggestionAdapterName::JsonPath {
+                path: parse(path)?,
+                description: description.map(parse).transpose()?,
+            });
+        }
+        if lower.starts_with("xml:") {
+            let path = s["xml:".len()..]
+                .trim()
+                .parse()
+                .map_err(|e: String| ConfigError::new(&e))?;
+            return Ok(SuggestionAdapterName::Xml(path));
+        }
+        if lower == "text" {
+            return Ok(SuggestionAdapterName::Text(None));
+        }
+        if lower.starts_with("text:") {
+            let pattern = s["text:".len()..]
+                .parse()
+                .map_err(|e: String| ConfigError::new(&e))?;
+            return Ok(SuggestionAdapterName::Text(Some(pattern)));
+        }
+        if lower.starts_with("exec:") {
+            let command = &s["exec:".len()..];
+            check_command(command)?;
+            return Ok(SuggestionAdapterName::Exec(command.to_string()));
+        }
+        if lower.starts_with("file:") {
+            let path = s["file:".len()..].trim();
+            if path.is_empty() {
+                return Err(Self::Err::new("File suggestion source needs a path."));
+            }
+            return Ok(SuggestionAdapterName::File(path.to_string()));
+        }
+        if let Some(custom) = CustomAdapter::from_registry(s).map_err(|e| Self::Err::new(&e))? {
+            return Ok(SuggestionAdapterName::Custom(custom));
         }
         return Err(Self::Err::new(&format!(
             "Unrecognized suggestion adapter string: {}",
@@ -247,6 +466,18 @@ pub enum BindableAction {
     Submit,
     ClearInput,
     AddChar(char),
+    MoveLeft,
+    MoveRight,
+    WordLeft,
+    WordRight,
+    Home,
+    End,
+    DeleteForward,
+    KillToEnd,
+    KillToStart,
+    Yank,
+    Undo,
+    Redo,
 }
 
 #[derive(Debug)]
@@ -274,30 +505,24 @@ impl Error for ConfigError {
     }
 }
 
-fn load_config_from_file() -> Result<Config, ConfigError> {
-    let proj_dirs = ProjectDirs::from("com", "efskap", "sefr")
-        .ok_or(ConfigError::new("Couldn't get config dir."))?;
-    let config_dir = proj_dirs.config_dir();
-    fs::create_dir_all(config_dir).or(Err(ConfigError::new(&format!(
-        "Couldn't create dir {:?}.",
-        config_dir
-    ))))?;
-    let config_path = config_dir.join("config.toml");
-    let default_toml = format!(
-        "{}",
-        toml::Value::try_from(&get_default_config()).or_else(|e| Err(ConfigError::new(
-            &format!(
+fn load_config_from_file(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let config_path = match path {
+        Some(path) => path.to_path_buf(),
+        None => default_config_path()?,
+    };
+    if path.is_none() && !config_path.exists() {
+        let default_toml = format!(
+            "{}",
+            toml::Value::try_from(get_default_config()).map_err(|e| ConfigError::new(&format!(
                 "Could not serialize default config to TOML. This... shouldn't happen. {:?}",
                 e
-            )
-        )))?
-    );
-    if !config_path.exists() {
+            )))?
+        );
         fs::write(&config_path, default_toml).or(Err(ConfigError::new(&format!(
             "Could not write default config to {:?}.",
             config_path
         ))))?;
-        println!(
+        eprintln!(
             "Wrote default config to {:?}. Edit it and enjoy!",
             config_path
         );
@@ -316,12 +541,17 @@ fn load_config_from_file() -> Result<Config, ConfigError> {
     })
 }
 
-fn validate_config(config: &mut Config) {
+fn validate_config(config: &mut Config) -> Result<(), ConfigError> {
     let default = config
         .engines
         .remove("_default")
-        .expect("No '_default' search engine found!!!");
+        .ok_or(ConfigError::new("No '_default' search engine found!!!"))?;
     config.engines.insert("".to_string(), default);
+    if config.suggestions.max_workers == 0 {
+        return Err(ConfigError::new(
+            "suggestions.max_workers has to be at least 1.",
+        ));
+    }
 
     // first fix em up
     for eng in config.engines.values_mut() {
@@ -330,12 +560,37 @@ fn validate_config(config: &mut Config) {
         if eng.prompt.text == DEFAULT_NAME {
             eng.prompt.text = format!(" {} ", eng.name);
         }
+        eng.http = eng.http.or(&config.http);
+        eng.parse_templates()
+            .map_err(|e| ConfigError::new(&format!("In engine '{}': {}", eng.name, e)))?;
+        if let Some(source) = eng.suggestion_sources.iter().find(|x| x.is_missing_url()) {
+            let e = format!(
+                "Engine '{}' has a '{}' suggestion source without a url.",
+                eng.name, source.adapter
+            );
+            return Err(ConfigError::new(&e));
+        }
+    }
+    // then get rid of invalid ones, except for the default engine, which has to be there
+    let default = &config.engines[""];
+    if default.search_url.is_empty() && default.command.is_empty() {
+        let e = format!(
+            "Engine '{}' needs either a search_url or a command.",
+            default.name
+        );
+        return Err(ConfigError::new(&e));
     }
-    // then get rid of invalid ones
     let bad_prefixes: Vec<String>  = config.engines
         .iter().filter_map(|(k,v)| {
             if k.contains(' ') {
-                println!("Prefixes have to be a single word, so engine '{}' with prefix '{}' will be ignored.", v.name, k);
+                eprintln!("Prefixes have to be a single word, so engine '{}' with prefix '{}' will be ignored.", v.name, k);
+                return Some(k.clone());
+            }
+            if v.search_url.is_empty() && v.command.is_empty() {
+                eprintln!(
+                    "Engine '{}' needs either a search_url or a command, so it will be ignored.",
+                    v.name
+                );
                 return Some(k.clone());
             }
             None
@@ -344,28 +599,53 @@ fn validate_config(config: &mut Config) {
     for prefix in bad_prefixes {
         config.engines.remove(&prefix);
     }
+    Ok(())
 }
 
-pub fn get_config() -> Config {
-    if let Some(proj_dirs) = ProjectDirs::from("com", "efskap", "sefr") {
-        proj_dirs.config_dir();
-    }
+fn default_config_path() -> Result<PathBuf, ConfigError> {
+    let proj_dirs = ProjectDirs::from("com", "efskap", "sefr")
+        .ok_or(ConfigError::new("Couldn't get config dir."))?;
+    let config_dir = proj_dirs.config_dir();
+    fs::create_dir_all(config_dir).or(Err(ConfigError::new(&format!(
+        "Couldn't create dir {:?}.",
+        config_dir
+    ))))?;
+    Ok(config_dir.join("config.toml"))
+}
+
+/// Loads and validates the config from `path`, or from the default location (writing the
+/// default config there first if it doesn't exist) when `path` is `None`.
+pub fn load_config(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let mut config = load_config_from_file(path)?;
+    validate_config(&mut config)?;
+    Ok(config)
+}
+
+/// Parses and validates a config from a TOML string.
+pub fn parse_config(toml: &str) -> Result<Config, ConfigError> {
+    let mut config = toml::from_str(toml)
+        .map_err(|e| ConfigError::new(&format!("Could not parse TOML: {}", e)))?;
+    validate_config(&mut config)?;
+    Ok(config)
+}
 
-    let mut config = match load_config_from_file() {
+/// Like `load_config`, but prints the error and falls back to the default config if loading fails.
+pub fn get_config(path: Option<&Path>) -> Config {
+    match load_config(path) {
         Ok(x) => x,
         Err(e) => {
-            println!(
+            eprintln!(
                 "{}{}Error!{} Could not load config:\n{}\nUsing default config.",
                 Attribute::Bold,
                 Colored::Fg(Color::Red),
                 Attribute::Reset,
                 e
             );
-            get_default_config()
+            let mut config = get_default_config();
+            validate_config(&mut config).expect("The default config is invalid.");
+            config
         }
-    };
-    validate_config(&mut config);
-    config
+    }
 }
 
 fn get_default_config() -> Config {
@@ -378,6 +658,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.google.com/search?q=%s".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Blue,
@@ -396,6 +685,15 @@ fn get_default_config() -> Config {
             search_url: "https://duckduckgo.com/?q=%s".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Rgb {
@@ -418,6 +716,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.google.com/search?btnI&q=%s".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Blue,
@@ -436,6 +743,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.google.com/search?q=site:reddit.com+%s".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Rgb {
@@ -458,6 +774,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.wiktionary.org/search-redirect.php?family=wiktionary&language=en&search=%s&go=Go".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::Black,
                 icon_bg: Color::White,
@@ -476,6 +801,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.wikipedia.org/search-redirect.php?family=wikipedia&language=en&search=%s&language=en&go=Go".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::Black,
                 icon_bg: Color::White,
@@ -496,6 +830,15 @@ fn get_default_config() -> Config {
             search_url: "https://www.youtube.com/results?q=%s".to_string(),
             space_becomes: "+".into(),
             suggestion_adapter: Default::default(),
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Red,
@@ -514,7 +857,19 @@ fn get_default_config() -> Config {
                 .to_string(),
             search_url: "https://www.reddit.com/r/%s".to_string(),
             space_becomes: "".into(), // subreddits dont have spaces
-            suggestion_adapter: SuggestionAdapterName::JsonPath("names".into()),
+            suggestion_adapter: SuggestionAdapterName::JsonPath {
+                path: "names".parse().unwrap(),
+                description: None,
+            },
+            open_with: None,
+            suggestion_sources: Vec::new(),
+            http: Default::default(),
+            params: Vec::new(),
+            param_separator: None,
+            param_defaults: Default::default(),
+            command: Vec::new(),
+            terminal: false,
+            parsed_templates: HashMap::new(),
             prompt: Prompt {
                 icon_fg: Color::White,
                 icon_bg: Color::Rgb {
@@ -542,12 +897,37 @@ fn get_default_config() -> Config {
     keybinds.insert(KeyBind(KeyEvent::BackTab), BindableAction::SelectPrev);
     keybinds.insert(KeyBind(KeyEvent::Up), BindableAction::SelectPrev);
     keybinds.insert(KeyBind(KeyEvent::Backspace), BindableAction::DeleteChar);
+    keybinds.insert(KeyBind(KeyEvent::Left), BindableAction::MoveLeft);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('b')), BindableAction::MoveLeft);
+    keybinds.insert(KeyBind(KeyEvent::Right), BindableAction::MoveRight);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('f')), BindableAction::MoveRight);
+    keybinds.insert(KeyBind(KeyEvent::Alt('b')), BindableAction::WordLeft);
+    keybinds.insert(KeyBind(KeyEvent::Alt('f')), BindableAction::WordRight);
+    keybinds.insert(KeyBind(KeyEvent::Home), BindableAction::Home);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('a')), BindableAction::Home);
+    keybinds.insert(KeyBind(KeyEvent::End), BindableAction::End);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('e')), BindableAction::End);
+    keybinds.insert(KeyBind(KeyEvent::Delete), BindableAction::DeleteForward);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('d')), BindableAction::DeleteForward);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('k')), BindableAction::KillToEnd);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('u')), BindableAction::KillToStart);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('y')), BindableAction::Yank);
+    keybinds.insert(KeyBind(KeyEvent::Ctrl('z')), BindableAction::Undo);
+    keybinds.insert(KeyBind(KeyEvent::Alt('z')), BindableAction::Redo);
 
     Config {
+        open_with: Default::default(),
+        history: true,
+        suggestions: Default::default(),
+        http: Default::default(),
         engines: engs,
         keybinds,
     }
 }
+fn _true() -> bool {
+    true
+}
+
 pub fn serialize_color<S>(x: &Color, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -572,6 +952,23 @@ where
     d.deserialize_any(ColorVisitor)
 }
 
+pub fn serialize_opt_color<S>(x: &Option<Color>, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match x {
+        Some(x) => serialize_color(x, s),
+        None => s.serialize_none(),
+    }
+}
+
+pub fn deserialize_opt_color<'de, D>(d: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_color(d).map(Some)
+}
+
 use serde::de::{self, Visitor};
 
 struct ColorVisitor;
@@ -633,3 +1030,98 @@ impl<'de> Visitor<'de> for ColorVisitor {
         Ok(Color::Rgb { r, g, b })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestion_adapter_round_trip() {
+        for name in &[
+            "opensearch",
+            "json:names",
+            "json:$.items[*].title|$.items[*].snippet",
+            "json:$['a|b'][*]|$['c|d'][*]",
+            "xml:toplevel/CompleteSuggestion/suggestion@data",
+            "text",
+            r"text:^\d+\t([^\t]+)",
+            "exec:git branch --format='%(refname:short)'",
+            "file:~/.config/sefr/subreddits.txt",
+        ] {
+            let parsed: SuggestionAdapterName = name.parse().unwrap();
+            assert_eq!(&parsed.to_string(), name);
+            assert!(parsed == parsed.to_string().parse().unwrap());
+        }
+        assert!(
+            "TEXT".parse::<SuggestionAdapterName>().unwrap() == SuggestionAdapterName::Text(None)
+        );
+        assert!("text:(".parse::<SuggestionAdapterName>().is_err());
+        assert!("exec:".parse::<SuggestionAdapterName>().is_err());
+        assert!("yaml:foo".parse::<SuggestionAdapterName>().is_err());
+    }
+
+    #[test]
+    fn open_with_round_trip() {
+        for s in &[
+            "browser",
+            "print",
+            "command:firefox --private-window {url}",
+            "terminal:w3m",
+            "clipboard:xclip -selection clipboard",
+        ] {
+            let parsed: OpenWith = s.parse().unwrap();
+            assert_eq!(&parsed.to_string(), s);
+        }
+        assert_eq!("Browser".parse::<OpenWith>().unwrap(), OpenWith::Browser);
+        assert_eq!(
+            "COMMAND:open -a Safari".parse::<OpenWith>().unwrap(),
+            OpenWith::Command("open -a Safari".into())
+        );
+        assert!("command:".parse::<OpenWith>().is_err());
+        assert!("terminal:  ".parse::<OpenWith>().is_err());
+        assert!("clipboard:xclip 'unclosed".parse::<OpenWith>().is_err());
+        assert!("firefox".parse::<OpenWith>().is_err());
+
+        let config = parse_config(
+            r#"
+            open_with = "print"
+
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+
+            [engines.w]
+            name = "Wikipedia"
+            search_url = "https://en.wikipedia.org/wiki/%s"
+            open_with = "terminal:w3m {url}"
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.open_with_for(&config.engines[""]), &OpenWith::Print);
+        let w = &config.engines["w"];
+        assert_eq!(
+            config.open_with_for(w),
+            &OpenWith::Terminal("w3m {url}".into())
+        );
+    }
+
+    #[test]
+    fn suggestion_settings() {
+        let engines = r#"
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+
+            [keybinds]
+            "#;
+        let config = parse_config(engines).unwrap();
+        assert_eq!(config.suggestions, SuggestionSettings::default());
+
+        let config = parse_config(&format!("[suggestions]\ndebounce_ms = 0\n{}", engines)).unwrap();
+        assert_eq!(config.suggestions.debounce_ms, 0);
+        assert_eq!(config.suggestions.max_workers, 4);
+        assert!(parse_config(&format!("[suggestions]\nmax_workers = 0\n{}", engines)).is_err());
+    }
+}
@@ -15,42 +15,184 @@
 // * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
 // *************************************************************************
 
-use crate::*;
 use crate::config::*;
+use crate::*;
 
-use std::fmt::{Formatter,Display};
+use percent_encoding::utf8_percent_encode;
 use serde::{Deserialize, Serialize};
-use percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::iter;
 
 const DEFAULT_NAME: &str = "%%DEFAULT%%";
 
-
-
-#[derive(Serialize, Deserialize)]
+/// A search engine, as defined by an `[engines.PREFIX]` table in the config.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Engine {
     #[serde(default = "default_prompt")]
     pub prompt: Prompt,
     pub name: String,
     #[serde(default)]
     pub suggestion_url: String,
+    /// Can be left out if the engine has a `command` instead.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub search_url: String,
-    #[serde(default = "_default_space_becomes", skip_serializing_if = "_is_default_space_becomes")]
+    #[serde(
+        default = "_default_space_becomes",
+        skip_serializing_if = "_is_default_space_becomes"
+    )]
     pub space_becomes: String,
+    /// Also accepted as `suggestion_source`, which reads better for sources like `file:`.
+    #[serde(
+        default,
+        skip_serializing_if = "is_default",
+        alias = "suggestion_source"
+    )]
+    pub suggestion_adapter: SuggestionAdapterName,
+    /// Overrides the global `open_with` for this engine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_with: Option<OpenWith>,
+    /// Several places to get suggestions from at once, instead of `suggestion_url` and
+    /// `suggestion_adapter`. See `Engine::sources`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestion_sources: Vec<SuggestionSource>,
+    /// Merged with the top-level `[http]` settings when the config is loaded.
     #[serde(default, skip_serializing_if = "is_default")]
-    pub suggestion_adapter: SuggestionAdapterName
+    pub http: HttpSettings,
+    /// Names for the parts the search term is split into, for `{name}` placeholders.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    /// What the search term is split at. Whitespace if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub param_separator: Option<String>,
+    /// Values for parts missing from the search term, by parameter name or number.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub param_defaults: BTreeMap<String, String>,
+    /// A program and its arguments to run on submit instead of opening `search_url`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+    /// Whether `command` runs in the terminal sefr was started in, like `man`.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub terminal: bool,
+    /// Every template of the engine, by its text, parsed once by `load_config` rather than on
+    /// every keystroke.
+    #[serde(skip)]
+    pub(crate) parsed_templates: HashMap<String, Template>,
 }
 
 impl Engine {
+    /// Percent-encodes `s` for a query string, with spaces turned into `space_becomes` as it is.
     fn encode(&self, s: &str) -> String {
-        utf8_percent_encode(&s.replace(" ", &self.space_becomes), DEFAULT_ENCODE_SET).to_string().replace("+", "%2B").into()
+        s.split(' ')
+            .map(|x| utf8_percent_encode(x, QUERY_COMPONENT_ENCODE_SET).to_string())
+            .collect::<Vec<_>>()
+            .join(&self.space_becomes.replace(' ', "%20"))
+    }
+    /// Whether there's anywhere to get suggestions from. Adapters like `exec` don't need a
+    /// `suggestion_url`.
+    pub fn has_suggestions(&self) -> bool {
+        !self.suggestion_url.is_empty() || !self.suggestion_adapter.uses_url()
     }
+
+    /// The URL to request suggestions from, with `%s` replaced by the encoded search term.
     pub fn format_suggestion_url(&self, search_term: &str) -> String {
-        self.suggestion_url
-            .replace("%s", &self.encode(&search_term))
+        self.format_url(&self.suggestion_url, search_term)
     }
+    /// Any URL template, with its placeholders filled in from the search term, encoded the way
+    /// this engine does. See `Template` for the syntax.
+    pub fn format_url(&self, template: &str, search_term: &str) -> String {
+        match self.template(template) {
+            Ok(template) => template.render(search_term, &self.param_values(search_term), |x| {
+                self.encode(x)
+            }),
+            // only engines built outside of `load_config` can get here
+            Err(_) => template.to_string(),
+        }
+    }
+    /// The URL to open for a search, with its placeholders filled in from the search term.
     pub fn format_search_url(&self, search_term: &str) -> String {
-        self.search_url
-            .replace("%s", &self.encode(&search_term))
+        self.format_url(&self.search_url, search_term)
+    }
+
+    /// The command to run for a search, with placeholders in each argument filled in like in
+    /// URL templates but without any encoding, and a leading `~` expanded. Arguments that come out
+    /// empty are left out, so that parameters can be optional. Empty if the engine opens URLs
+    /// instead.
+    pub fn format_command(&self, search_term: &str) -> Vec<String> {
+        let values = self.param_values(search_term);
+        self.command
+            .iter()
+            .filter_map(|arg| {
+                let arg = match self.template(arg) {
+                    Ok(template) => template.render(search_term, &values, str::to_string),
+                    Err(_) => arg.clone(),
+                };
+                if arg.is_empty() {
+                    None
+                } else if arg.starts_with('~') {
+                    Some(expand_tilde(&arg).to_string_lossy().into_owned())
+                } else {
+                    Some(arg)
+                }
+            })
+            .collect()
+    }
+
+    fn templates(&self) -> impl Iterator<Item = &str> {
+        iter::once(self.search_url.as_str())
+            .chain(iter::once(self.suggestion_url.as_str()))
+            .chain(self.suggestion_sources.iter().map(|x| x.url.as_str()))
+            .chain(self.command.iter().map(String::as_str))
+    }
+
+    /// Parses every template of the engine, checking that they only use parameters it has.
+    pub fn parse_templates(&mut self) -> Result<(), String> {
+        let mut parsed = HashMap::new();
+        for template in self.templates() {
+            parsed.insert(
+                template.to_string(),
+                Template::parse(template, &self.params)?,
+            );
+        }
+        self.parsed_templates = parsed;
+        Ok(())
+    }
+
+    /// `template` parsed, without parsing it again if `parse_templates` already did.
+    fn template(&self, template: &str) -> Result<Cow<'_, Template>, String> {
+        match self.parsed_templates.get(template) {
+            Some(parsed) => Ok(Cow::Borrowed(parsed)),
+            None => Template::parse(template, &self.params).map(Cow::Owned),
+        }
+    }
+
+    /// The parts of the search term for the engine's parameters, with defaults for missing ones.
+    /// The term is split into as many parts as the engine has names for, or as the highest
+    /// numbered placeholder in any of its templates, whichever is more.
+    pub fn param_values(&self, search_term: &str) -> Vec<String> {
+        let count = self
+            .templates()
+            .filter_map(|x| self.template(x).ok())
+            .map(|x| x.param_count())
+            .chain(Some(self.params.len()))
+            .max()
+            .unwrap_or(0);
+        let mut values = split_params(search_term, self.param_separator.as_deref(), count);
+        values.resize(count, String::new());
+        for (i, value) in values.iter_mut().enumerate() {
+            if value.is_empty() {
+                let default = self
+                    .params
+                    .get(i)
+                    .and_then(|name| self.param_defaults.get(name))
+                    .or_else(|| self.param_defaults.get(&(i + 1).to_string()));
+                if let Some(default) = default {
+                    *value = default.clone();
+                }
+            }
+        }
+        values
     }
 }
 
@@ -91,17 +233,23 @@ impl Prompt {
         ShortPrompt(self)
     }
 
+    /// How many columns the prompt takes up when displayed.
+    pub fn width(&self) -> usize {
+        self.icon.chars().count() + self.text.chars().count()
+    }
 }
 impl Display for Prompt {
     fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-            write!(f,
+        write!(
+            f,
             "{}{}{}{}",
             Colored::Fg(self.icon_fg),
             Colored::Bg(self.icon_bg),
             self.icon,
             Attribute::Reset
         )?; // icon
-        write!(f,
+        write!(
+            f,
             "{}{}{}{}",
             Colored::Fg(self.text_fg),
             Colored::Bg(self.text_bg),
@@ -112,11 +260,18 @@ impl Display for Prompt {
     }
 }
 
-pub struct ShortPrompt<'a> (&'a Prompt);
+pub struct ShortPrompt<'a>(&'a Prompt);
+
+impl<'a> ShortPrompt<'a> {
+    pub fn width(&self) -> usize {
+        self.0.icon.chars().count()
+    }
+}
 
 impl<'a> Display for ShortPrompt<'a> {
-    fn fmt(& self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-            write!(f,
+    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
             "{}{}{}{}",
             Colored::Fg(self.0.icon_fg),
             Colored::Bg(self.0.icon_bg),
@@ -127,6 +282,10 @@ impl<'a> Display for ShortPrompt<'a> {
     }
 }
 
+/// Picks the engine for an input line based on its first word, returning the engine, the prefix
+/// that triggered it (empty for the default engine) and the search term with the prefix removed.
+///
+/// `engines` must be validated (see `load_config`), i.e. have the default engine under `""`.
 // TODO: this should prolly return slices, not Strings
 pub fn match_engine<'a, 'b>(
     input_line: &'b str,
@@ -156,6 +315,88 @@ pub fn match_engine<'a, 'b>(
     }
 }
 
+/// Top-level domains that bare domains like `example.com` are recognized by. Others need a scheme,
+/// port or path to be taken for a URL, so that searches like `node.js` stay searches. Ones that
+/// are also common file extensions (`.rs`, `.sh`, `.so`, `.in`, ...) are left out on purpose, so
+/// that `main.rs` is searched for while `docs.rs/serde` is still opened.
+const COMMON_TLDS: &[&str] = &[
+    "at", "be", "biz", "ca", "ch", "cn", "co", "com", "cz", "de", "dev", "dk", "edu", "es", "eu",
+    "fi", "fm", "fr", "gg", "gov", "io", "it", "jp", "kr", "me", "net", "nl", "no", "nz", "org",
+    "ru", "se", "tv", "uk", "us", "xyz",
+];
+
+/// If `input_line` looks like a URL (`https://...`) or a bare domain (`docs.rs/serde`,
+/// `localhost:8080`) rather than a search term, returns the URL to open for it. Like in
+/// `match_engine`, a `?` in front makes it a search.
+pub fn detect_url(input_line: &str) -> Option<String> {
+    let s = input_line.trim();
+    if s.is_empty() || s.starts_with('?') || s.contains(char::is_whitespace) {
+        return None;
+    }
+    if let Some(i) = s.find("://") {
+        let scheme = &s[..i];
+        let is_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c));
+        return if is_scheme && s.len() > i + 3 {
+            Some(s.to_string())
+        } else {
+            None
+        };
+    }
+    let end = s.find(['/', '?', '#']).unwrap_or(s.len());
+    let (host, port) = match s[..end].rfind(':') {
+        Some(i) => (&s[..i], Some(&s[i + 1..end])),
+        None => (&s[..end], None),
+    };
+    if let Some(port) = port {
+        if port.is_empty() || port.len() > 5 || !port.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+    }
+    if host.eq_ignore_ascii_case("localhost") || is_ipv4(host) {
+        return Some(format!("http://{}", s));
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let is_domain = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    let tld = labels.last().unwrap_or(&"").to_lowercase();
+    let is_tld = tld.chars().count() >= 2 && tld.chars().all(char::is_alphabetic);
+    let explicit = port.is_some() || end < s.len();
+    if is_domain && is_tld && (explicit || COMMON_TLDS.contains(&tld.as_str())) {
+        Some(format!("https://{}", s))
+    } else {
+        None
+    }
+}
+
+fn is_ipv4(host: &str) -> bool {
+    let parts: Vec<&str> = host.split('.').collect();
+    parts.len() == 4
+        && parts
+            .iter()
+            .all(|x| !x.is_empty() && x.parse::<u8>().is_ok())
+}
+
+/// Like `match_engine`, but if `locked_prefix` is set, that engine is always used and the whole
+/// input line is its search term.
+pub fn match_engine_or_locked<'a>(
+    input_line: &str,
+    engines: &'a HashMap<String, Engine>,
+    locked_prefix: Option<&str>,
+) -> (&'a Engine, String, String) {
+    match locked_prefix.and_then(|prefix| engines.get_key_value(prefix)) {
+        Some((prefix, engine)) => (engine, prefix.clone(), input_line.trim().to_string()),
+        None => match_engine(input_line, engines),
+    }
+}
+
 fn default_prompt() -> Prompt {
     Prompt {
         icon_fg: Color::White,
@@ -185,3 +426,98 @@ fn _white() -> Color {
 fn _black() -> Color {
     Color::Black
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let config = parse_config(
+            r#"
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+
+            [engines.man]
+            name = "man"
+            command = ["man", "%1", "{2}"]
+            terminal = true
+
+            [engines.n]
+            name = "Notes"
+            command = ["xdg-open", "~/notes/{query|lower}.md"]
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        let man = &config.engines["man"];
+        assert!(man.terminal);
+        assert_eq!(man.format_command("3 printf"), vec!["man", "3", "printf"]);
+        assert_eq!(man.format_command("ls"), vec!["man", "ls"]);
+
+        let notes = config.engines["n"].format_command("Rust & C++");
+        assert!(notes[1].ends_with("/notes/rust & c++.md"));
+        assert!(!notes[1].starts_with('~'));
+        assert!(config.engines[""].format_command("x").is_empty());
+
+        // an engine with neither is skipped, unless it's the default one
+        let neither = "[engines._default]\nname = \"x\"\n[keybinds]\n";
+        assert!(parse_config(neither).is_err());
+        let config = parse_config(
+            "[engines._default]\nname = \"x\"\nsearch_url = \"https://x/?q=%s\"\n\
+             [engines.y]\nname = \"y\"\n[keybinds]\n",
+        )
+        .unwrap();
+        assert!(!config.engines.contains_key("y"));
+        assert!(config.engines.contains_key(""));
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            detect_url(" docs.rs/serde "),
+            Some("https://docs.rs/serde".into())
+        );
+        assert_eq!(
+            detect_url("example.com"),
+            Some("https://example.com".into())
+        );
+        assert_eq!(
+            detect_url("localhost:8080/x"),
+            Some("http://localhost:8080/x".into())
+        );
+        assert_eq!(detect_url("192.168.1.1"), Some("http://192.168.1.1".into()));
+        assert_eq!(detect_url("https://a.b/c d"), None);
+        assert_eq!(detect_url("ftp://x"), Some("ftp://x".into()));
+        assert_eq!(
+            detect_url("intranet.corp:8443"),
+            Some("https://intranet.corp:8443".into())
+        );
+        assert_eq!(detect_url("bücher.de"), Some("https://bücher.de".into()));
+        for search in &[
+            "rust",
+            "node.js",
+            "3.14",
+            "e.g.",
+            "U.S.A",
+            "me@example.com",
+            "?docs.rs/serde",
+            "rust lang.org",
+            "localhost:http",
+            "://x",
+            "1.2.3.400",
+            "main.rs",
+            "install.sh",
+            "libssl.so",
+            "Makefile.in",
+            "script.pl",
+            "logo.ai",
+            "index.app",
+            "notes.info",
+        ] {
+            assert_eq!(detect_url(search), None, "{}", search);
+        }
+    }
+}
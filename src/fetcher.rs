@@ -0,0 +1,159 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::UiMsg;
+use sefr::*;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// Asking one of an engine's sources for suggestions.
+pub struct Job {
+    pub engine: Engine,
+    pub prefix: String,
+    pub source_index: usize,
+    pub source: SuggestionSource,
+    pub search_term: String,
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<(u64, Job)>>,
+    available: Condvar,
+    /// Jobs for any other generation are dropped instead of being run or reported.
+    generation: AtomicU64,
+}
+
+/// A fixed number of threads that fetch suggestions (through `cache`) and send them to the UI as
+/// `UiMsg::SetSuggestions`, so that typing quickly can't pile up requests.
+pub struct Fetcher {
+    shared: Arc<Shared>,
+}
+
+impl Fetcher {
+    pub fn new(workers: usize, cache: Arc<Mutex<SuggestionCache>>, tx: Sender<UiMsg>) -> Fetcher {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            available: Condvar::new(),
+            generation: AtomicU64::new(0),
+        });
+        for _ in 0..workers {
+            let shared = shared.clone();
+            let cache = cache.clone();
+            let tx = tx.clone();
+            // the workers just wait on the queue once the UI is gone, until the process exits
+            thread::spawn(move || loop {
+                let (generation, job) = {
+                    let mut queue = shared.queue.lock().unwrap();
+                    loop {
+                        match queue.pop_front() {
+                            Some(x) => break x,
+                            None => queue = shared.available.wait(queue).unwrap(),
+                        }
+                    }
+                };
+                if generation != shared.generation.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let result = fetch_cached(
+                    &cache,
+                    &job.engine,
+                    &job.prefix,
+                    &job.source,
+                    None,
+                    &job.search_term,
+                );
+                // a request can't be stopped halfway, but its result can still be thrown away
+                if generation != shared.generation.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let _ = tx.send(UiMsg::SetSuggestions {
+                    generation,
+                    source: job.source_index,
+                    result,
+                });
+            });
+        }
+        Fetcher { shared }
+    }
+
+    /// Abandons every job from before `generation`, queued or running, and queues `jobs` for it.
+    pub fn submit(&self, generation: u64, jobs: Vec<Job>) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        self.shared.generation.store(generation, Ordering::SeqCst);
+        queue.clear();
+        queue.extend(jobs.into_iter().map(|job| (generation, job)));
+        self.shared.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn superseded_jobs_are_dropped() {
+        let config = parse_config(
+            r#"
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+
+            [[engines._default.suggestion_sources]]
+            adapter = "exec:sh -c 'sleep 0.3; echo slow'"
+
+            [[engines._default.suggestion_sources]]
+            adapter = "exec:echo fast"
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        let engine = &config.engines[""];
+        let sources = engine.sources(false);
+        let job = |n: usize| Job {
+            engine: engine.clone(),
+            prefix: String::new(),
+            source_index: n,
+            source: sources[n].clone(),
+            search_term: "x".into(),
+        };
+
+        let (tx, rx) = mpsc::channel();
+        let cache = Arc::new(Mutex::new(SuggestionCache::new(0)));
+        let fetcher = Fetcher::new(1, cache, tx);
+        // the only worker is busy with the slow one while the rest get replaced
+        fetcher.submit(1, vec![job(0), job(1), job(1)]);
+        thread::sleep(Duration::from_millis(50));
+        fetcher.submit(2, vec![job(1)]);
+
+        let mut received = Vec::new();
+        while let Ok(UiMsg::SetSuggestions {
+            generation,
+            source,
+            result,
+        }) = rx.recv_timeout(Duration::from_millis(600))
+        {
+            received.push((generation, source, result.unwrap().sugg_terms));
+        }
+        assert_eq!(received, vec![(2, 1, vec!["fast".to_string()])]);
+    }
+}
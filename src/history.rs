@@ -0,0 +1,231 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::*;
+
+use directories::ProjectDirs;
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How many past queries get blended into the suggestions at most.
+const MAX_HISTORY_SUGGESTIONS: usize = 5;
+
+/// How many of the most recently used queries are kept on disk.
+const MAX_SAVED_ENTRIES: usize = 1000;
+
+const DAY: u64 = 60 * 60 * 24;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Prefix of the engine the query was submitted to (empty for the default engine).
+    pub prefix: String,
+    pub query: String,
+    pub count: u32,
+    /// Unix timestamp (in seconds) of the last time the query was submitted.
+    pub last_used: u64,
+}
+
+impl HistoryEntry {
+    /// Firefox-style frecency: the number of visits, weighted by how recent the last one was.
+    fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_used);
+        let weight = if age < DAY {
+            4.0
+        } else if age < 7 * DAY {
+            2.0
+        } else if age < 30 * DAY {
+            1.0
+        } else {
+            0.5
+        };
+        f64::from(self.count) * weight
+    }
+}
+
+/// Submitted queries per engine prefix, stored as tab-separated lines of
+/// `prefix, count, last_used, query`.
+#[derive(Debug, Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// Loads the history from `history.tsv` in the data dir. Starts out empty if it doesn't exist.
+    pub fn load() -> io::Result<History> {
+        let proj_dirs = ProjectDirs::from("com", "efskap", "sefr")
+            .ok_or_else(|| io::Error::other("Couldn't get data dir."))?;
+        History::load_from(&proj_dirs.data_dir().join("history.tsv"))
+    }
+
+    pub fn load_from(path: &Path) -> io::Result<History> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(History {
+            path: Some(path.to_path_buf()),
+            entries: text.lines().filter_map(parse_line).collect(),
+        })
+    }
+
+    /// Writes the most recently used queries back to the file the history was loaded from, without
+    /// ever leaving it half-written. Does nothing for a history that wasn't loaded from a file.
+    pub fn save(&self) -> io::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|e| Reverse(e.last_used));
+        let text: String = entries
+            .into_iter()
+            .take(MAX_SAVED_ENTRIES)
+            .map(|e| format!("{}\t{}\t{}\t{}\n", e.prefix, e.count, e.last_used, e.query))
+            .collect();
+        write_atomically(path, &text)
+    }
+
+    /// Records a submitted query for the engine with `prefix`.
+    pub fn record(&mut self, prefix: &str, query: &str) {
+        self.record_at(prefix, query, unix_now());
+    }
+
+    fn record_at(&mut self, prefix: &str, query: &str, now: u64) {
+        // tabs and newlines would break the file format
+        let query = query.replace(['\t', '\n', '\r'], " ").trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.prefix == prefix && e.query == query)
+        {
+            Some(entry) => {
+                entry.count += 1;
+                entry.last_used = now;
+            }
+            None => self.entries.push(HistoryEntry {
+                prefix: prefix.to_string(),
+                query,
+                count: 1,
+                last_used: now,
+            }),
+        }
+    }
+
+    /// Past queries for the engine with `prefix` that start with `term` (ignoring case), best
+    /// frecency first.
+    pub fn matches(&self, prefix: &str, term: &str) -> Vec<String> {
+        self.matches_at(prefix, term, unix_now())
+    }
+
+    fn matches_at(&self, prefix: &str, term: &str, now: u64) -> Vec<String> {
+        let term = term.to_lowercase();
+        let mut matching: Vec<&HistoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.prefix == prefix && e.query.to_lowercase().starts_with(&term))
+            .collect();
+        matching.sort_by(|a, b| {
+            b.frecency(now)
+                .partial_cmp(&a.frecency(now))
+                .unwrap()
+                .then(b.last_used.cmp(&a.last_used))
+        });
+        matching
+            .into_iter()
+            .take(MAX_HISTORY_SUGGESTIONS)
+            .map(|e| e.query.clone())
+            .collect()
+    }
+}
+
+fn parse_line(line: &str) -> Option<HistoryEntry> {
+    let mut fields = line.splitn(4, '\t');
+    Some(HistoryEntry {
+        prefix: fields.next()?.to_string(),
+        count: fields.next()?.parse().ok()?,
+        last_used: fields.next()?.parse().ok()?,
+        query: fields.next()?.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frecency_order() {
+        let now = 100 * DAY;
+        let mut history = History::default();
+        // used often but long ago
+        for _ in 0..3 {
+            history.record_at("yt", "rust conf", now - 60 * DAY);
+        }
+        history.record_at("yt", "rust lang", now - 60);
+        history.record_at("yt", "rust lang", now - 60);
+        history.record_at("yt", "Rustacean", now - 2 * DAY);
+        history.record_at("w", "rust", now);
+
+        assert_eq!(
+            history.matches_at("yt", "rust", now),
+            vec!["rust lang", "Rustacean", "rust conf"]
+        );
+        assert_eq!(history.matches_at("", "rust", now), Vec::<String>::new());
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("sefr-history-{}.tsv", std::process::id()));
+        let mut history = History::load_from(&path).unwrap();
+        history.record_at("", "tabs\tare bad", 42);
+        history.record_at("yt", "rust", 43);
+        history.record_at("yt", "rust", 44);
+        history.save().unwrap();
+
+        let loaded = History::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries.len(), 2);
+        for entry in &history.entries {
+            assert!(loaded.entries.contains(entry));
+        }
+        assert_eq!(history.entries[0].query, "tabs are bad");
+        assert_eq!(parse_line("garbage"), None);
+    }
+
+    #[test]
+    fn saved_entries_are_capped() {
+        let path = std::env::temp_dir().join(format!("sefr-capped-{}.tsv", std::process::id()));
+        let mut history = History::load_from(&path).unwrap();
+        for n in 0..MAX_SAVED_ENTRIES + 10 {
+            history.record_at("", &format!("query {}", n), n as u64);
+        }
+        history.save().unwrap();
+
+        let loaded = History::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries.len(), MAX_SAVED_ENTRIES);
+        // the oldest ones are dropped
+        assert!(!loaded.entries.iter().any(|e| e.query == "query 9"));
+        assert!(loaded.entries.iter().any(|e| e.query == "query 10"));
+    }
+}
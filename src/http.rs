@@ -0,0 +1,343 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 250;
+
+/// An agent for each combination of settings that requests have been made with, so that
+/// connections are kept open and reused between keystrokes.
+static AGENTS: LazyLock<Mutex<HashMap<AgentSettings, ureq::Agent>>> =
+    LazyLock::new(Default::default);
+
+/// The settings that go into building an agent, rather than into each request.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct AgentSettings {
+    timeout_secs: u64,
+    user_agent: String,
+    proxy: Option<String>,
+}
+
+/// How suggestion requests are made, as set in an `[http]` table, either at the top level of the
+/// config or in an engine. Anything an engine leaves out comes from the top-level one.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpSettings {
+    /// How many seconds the whole request, connecting included, may take before giving up. Also
+    /// limits how long `exec:` suggestion commands can run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Extra headers to send. `${VAR}` in a value is replaced by the environment variable `VAR`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// How many more times to try after a network error or a 429 or 5xx response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// How long to wait before the first retry. Doubles for each one after that.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_backoff_ms: Option<u64>,
+    /// Proxy to use instead of the one from `$HTTPS_PROXY`/`$HTTP_PROXY`. Empty for none at all.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+impl HttpSettings {
+    /// These settings, with anything they leave out taken from `fallback`. Headers from both are
+    /// sent, with the ones from `self` winning.
+    pub fn or(&self, fallback: &HttpSettings) -> HttpSettings {
+        let mut headers = fallback.headers.clone();
+        headers.extend(self.headers.clone());
+        HttpSettings {
+            timeout_secs: self.timeout_secs.or(fallback.timeout_secs),
+            user_agent: self
+                .user_agent
+                .clone()
+                .or_else(|| fallback.user_agent.clone()),
+            headers,
+            retries: self.retries.or(fallback.retries),
+            retry_backoff_ms: self.retry_backoff_ms.or(fallback.retry_backoff_ms),
+            proxy: self.proxy.clone().or_else(|| fallback.proxy.clone()),
+        }
+    }
+
+    /// The proxy that requests to `url` go through, if any. `var` looks up environment variables,
+    /// so that `$NO_PROXY` and the proxy for the URL's scheme can be taken into account.
+    fn proxy_for(&self, url: &str, var: impl Fn(&str) -> Option<String>) -> Option<String> {
+        let var = |name: &str| {
+            var(&name.to_lowercase())
+                .or_else(|| var(name))
+                .filter(|x| !x.is_empty())
+        };
+        let proxy = match self.proxy {
+            Some(ref proxy) => Some(proxy.clone()).filter(|x| !x.is_empty()),
+            None if url_scheme(url).eq_ignore_ascii_case("https") => var("HTTPS_PROXY"),
+            None => var("HTTP_PROXY"),
+        }?;
+        let host = url_host(url);
+        let bypassed = var("NO_PROXY").is_some_and(|no_proxy| {
+            no_proxy.split(',').any(|pattern| {
+                let pattern = pattern.trim().trim_start_matches('.').to_lowercase();
+                let pattern = url_host(&pattern);
+                pattern == "*"
+                    || (!pattern.is_empty()
+                        && (host == pattern || host.ends_with(&format!(".{}", pattern))))
+            })
+        });
+        if bypassed {
+            None
+        } else {
+            Some(proxy)
+        }
+    }
+
+    /// The agent for these settings and `proxy`, built the first time it's needed.
+    fn agent(&self, proxy: Option<&str>) -> Result<ureq::Agent, SuggestionError> {
+        let settings = AgentSettings {
+            timeout_secs: self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS),
+            user_agent: match self.user_agent {
+                Some(ref user_agent) => user_agent.clone(),
+                None => format!("sefr/{}", env!("CARGO_PKG_VERSION")),
+            },
+            proxy: proxy.map(str::to_string),
+        };
+        let mut agents = AGENTS.lock().unwrap();
+        if let Some(agent) = agents.get(&settings) {
+            return Ok(agent.clone());
+        }
+        let mut builder = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(settings.timeout_secs))
+            .user_agent(&settings.user_agent);
+        if let Some(proxy) = proxy {
+            let proxy = ureq::Proxy::new(proxy).map_err(|e| {
+                SuggestionError::Config(format!("invalid proxy '{}': {}", proxy, e))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+        let agent = builder.build();
+        agents.insert(settings, agent.clone());
+        Ok(agent)
+    }
+
+    fn request(&self, url: &str, proxy: Option<&str>) -> Result<ureq::Request, SuggestionError> {
+        let mut request = self.agent(proxy)?.get(url);
+        for (name, value) in &self.headers {
+            let value = interpolate_env(value).map_err(SuggestionError::Config)?;
+            request = request.set(name, &value);
+        }
+        Ok(request)
+    }
+}
+
+/// GETs `url` with `http`'s settings and returns the body, retrying if it's set up to.
+pub fn http_get(url: &str, http: &HttpSettings) -> Result<String, SuggestionError> {
+    let mut backoff =
+        Duration::from_millis(http.retry_backoff_ms.unwrap_or(DEFAULT_RETRY_BACKOFF_MS));
+    let mut retries = http.retries.unwrap_or(0);
+    let proxy = http.proxy_for(url, |var| env::var(var).ok());
+    let network_error = |e: String| match proxy {
+        Some(ref proxy) => SuggestionError::Network(format!("{} (through proxy {})", e, proxy)),
+        None => SuggestionError::Network(e),
+    };
+    let request = http.request(url, proxy.as_deref())?;
+    loop {
+        let result = match request.clone().call() {
+            Ok(response) => response
+                .into_string()
+                .map_err(|e| network_error(e.to_string())),
+            Err(ureq::Error::Status(status, _)) => {
+                Err(SuggestionError::HttpStatus(i32::from(status)))
+            }
+            Err(e) => Err(network_error(e.to_string())),
+        };
+        let retryable = match result {
+            Err(SuggestionError::Network(_)) => true,
+            Err(SuggestionError::HttpStatus(status)) => status == 429 || status >= 500,
+            _ => false,
+        };
+        if !retryable || retries == 0 {
+            return result;
+        }
+        thread::sleep(backoff);
+        backoff *= 2;
+        retries -= 1;
+    }
+}
+
+fn url_scheme(url: &str) -> &str {
+    url.find("://").map_or("", |i| &url[..i])
+}
+
+/// The lowercased host name of `url`, without the scheme, user info, port or path. Also works on
+/// a bare `host:port`.
+fn url_host(url: &str) -> String {
+    let rest = url.find("://").map_or(url, |i| &url[i + 3..]);
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    let host = &authority[authority.rfind('@').map_or(0, |i| i + 1)..];
+    let host = if host.starts_with('[') {
+        &host[..host.find(']').map_or(host.len(), |i| i + 1)]
+    } else {
+        &host[..host.find(':').unwrap_or(host.len())]
+    };
+    host.to_lowercase()
+}
+
+/// Replaces each `${VAR}` in `s` with the environment variable `VAR`.
+fn interpolate_env(s: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '${{' in header value '{}'", s))?;
+        let var = &rest[start + 2..start + end];
+        let value = env::var(var).map_err(|_| format!("environment variable {} isn't set", var))?;
+        out.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn engine_settings_override_global_ones() {
+        let config = parse_config(
+            r#"
+            [http]
+            timeout_secs = 3
+            user_agent = "sefr-test"
+            headers = { Accept = "application/json", "X-Team" = "search" }
+
+            [engines._default]
+            name = "Internal"
+            search_url = "https://search.internal/?q=%s"
+
+            [engines._default.http]
+            retries = 2
+            headers = { Authorization = "Bearer ${SEFR_TOKEN}", Accept = "text/plain" }
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        let http = &config.engines[""].http;
+        assert_eq!(http.timeout_secs, Some(3));
+        assert_eq!(http.user_agent.as_deref(), Some("sefr-test"));
+        assert_eq!(http.retries, Some(2));
+        assert_eq!(http.headers["Accept"], "text/plain");
+        assert_eq!(http.headers["X-Team"], "search");
+        assert_eq!(http.headers["Authorization"], "Bearer ${SEFR_TOKEN}");
+    }
+
+    #[test]
+    fn proxy_selection() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| pairs.iter().find(|x| x.0 == name).map(|x| x.1.to_string())
+        };
+        let env = vars(&[
+            ("HTTPS_PROXY", "http://secure.proxy:3128"),
+            ("http_proxy", "http://plain.proxy:3128"),
+            ("NO_PROXY", "localhost, .internal,10.0.0.1:8080"),
+        ]);
+        let http = HttpSettings::default();
+        let proxy = |http: &HttpSettings, url| http.proxy_for(url, env);
+        let secure = Some("http://secure.proxy:3128");
+        assert_eq!(proxy(&http, "https://a.com/?q=x").as_deref(), secure);
+        assert_eq!(
+            proxy(&http, "http://a.com").as_deref(),
+            Some("http://plain.proxy:3128")
+        );
+        assert_eq!(proxy(&http, "http://user@LOCALHOST:8080/x"), None);
+        assert_eq!(proxy(&http, "https://search.internal/x"), None);
+        assert_eq!(proxy(&http, "https://internal"), None);
+        assert!(proxy(&http, "https://notinternal.com").is_some());
+        assert_eq!(proxy(&http, "http://10.0.0.1/x"), None);
+
+        let own = HttpSettings {
+            proxy: Some("http://own.proxy:8080".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            proxy(&own, "http://a.com").as_deref(),
+            Some("http://own.proxy:8080")
+        );
+        assert_eq!(proxy(&own, "https://search.internal"), None);
+        let none = HttpSettings {
+            proxy: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(proxy(&none, "https://a.com"), None);
+        assert_eq!(
+            HttpSettings::default().proxy_for("https://a.com", |_| None),
+            None
+        );
+        assert_eq!(none.or(&own).proxy, Some(String::new()));
+    }
+
+    #[test]
+    fn env_interpolation() {
+        env::set_var("SEFR_TEST_TOKEN", "hunter2");
+        assert_eq!(
+            interpolate_env("Bearer ${SEFR_TEST_TOKEN}!"),
+            Ok("Bearer hunter2!".into())
+        );
+        assert_eq!(interpolate_env("no vars"), Ok("no vars".into()));
+        assert!(interpolate_env("${SEFR_TEST_UNSET_VAR}").is_err());
+        assert!(interpolate_env("${SEFR_TEST_TOKEN").is_err());
+    }
+
+    #[test]
+    fn bad_settings_are_not_retried() {
+        let http = HttpSettings {
+            retries: Some(3),
+            retry_backoff_ms: Some(1000),
+            proxy: Some("ftp://proxy.invalid".into()),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        match http_get("http://localhost:9/", &http) {
+            Err(SuggestionError::Config(_)) => {}
+            other => panic!("expected a config error, got {:?}", other),
+        }
+        let mut headers = BTreeMap::new();
+        headers.insert("X-Token".into(), "${SEFR_TEST_UNSET_VAR}".into());
+        let http = HttpSettings {
+            headers,
+            proxy: None,
+            ..http
+        };
+        match http_get("http://localhost:9/", &http) {
+            Err(SuggestionError::Config(_)) => {}
+            other => panic!("expected a config error, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_millis(1000));
+    }
+}
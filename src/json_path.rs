@@ -0,0 +1,301 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use json::JsonValue;
+use std::fmt;
+use std::str::FromStr;
+
+/// What fields missing after a wildcard turn into, so the results of two paths still line up.
+static NULL: JsonValue = JsonValue::Null;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// `.name` or `["name"]`
+    Field(String),
+    /// `[0]`
+    Index(usize),
+    /// `[*]` or `.*`: every element of an array, or every value of an object.
+    Wildcard,
+    /// A JSON Pointer token: an array index if the value is an array, otherwise a field.
+    Token(String),
+}
+
+/// A path into a JSON document, either in a JSONPath subset (`$.data.children[*].name`,
+/// `items[0]["title"]`) or as a JSON Pointer (`/data/children/0/name`).
+///
+/// The leading `$` is optional, so plain dotted paths like `data.names` work too.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    source: String,
+    /// Each step along with where it ends in `source`, for error messages.
+    steps: Vec<(Step, usize)>,
+}
+
+impl JsonPath {
+    pub fn has_wildcard(&self) -> bool {
+        self.steps.iter().any(|(step, _)| *step == Step::Wildcard)
+    }
+
+    /// Every value the path leads to, in document order. Without a wildcard that's at most one.
+    ///
+    /// If a step before any wildcard leads nowhere, returns the part of the path up to and
+    /// including that step as the error. Steps after a wildcard that lead nowhere give `null`s
+    /// instead, so that results stay in the same order as the elements they came from.
+    pub fn select<'a>(&self, root: &'a JsonValue) -> Result<Vec<&'a JsonValue>, String> {
+        let mut current = vec![root];
+        let mut after_wildcard = false;
+        for (step, end) in &self.steps {
+            let mut next = Vec::new();
+            for value in current {
+                match step {
+                    Step::Wildcard => match value {
+                        JsonValue::Array(items) => next.extend(items),
+                        JsonValue::Object(object) => next.extend(object.iter().map(|(_, x)| x)),
+                        _ => {}
+                    },
+                    _ => match child(value, step) {
+                        Some(x) => next.push(x),
+                        None if after_wildcard => next.push(&NULL),
+                        None => return Err(self.source[..*end].to_string()),
+                    },
+                }
+            }
+            after_wildcard |= *step == Step::Wildcard;
+            current = next;
+        }
+        Ok(current)
+    }
+}
+
+fn child<'a>(value: &'a JsonValue, step: &Step) -> Option<&'a JsonValue> {
+    match (step, value) {
+        (Step::Field(name), JsonValue::Object(object)) => object.get(name),
+        (Step::Index(i), JsonValue::Array(items)) => items.get(*i),
+        (Step::Token(token), JsonValue::Array(items)) => items.get(token.parse::<usize>().ok()?),
+        (Step::Token(token), JsonValue::Object(object)) => object.get(token),
+        _ => None,
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let steps = if s.starts_with('/') {
+            parse_pointer(s)
+        } else {
+            parse_path(s)?
+        };
+        Ok(JsonPath {
+            source: s.to_string(),
+            steps,
+        })
+    }
+}
+
+fn parse_pointer(s: &str) -> Vec<(Step, usize)> {
+    let mut end = 0;
+    s.split('/')
+        .skip(1)
+        .map(|token| {
+            end += 1 + token.len();
+            let token = token.replace("~1", "/").replace("~0", "~");
+            (Step::Token(token), end)
+        })
+        .collect()
+}
+
+fn parse_path(s: &str) -> Result<Vec<(Step, usize)>, String> {
+    let err = |i: usize, msg: &str| Err(format!("{} at position {} of JSON path '{}'", msg, i, s));
+    let bytes = s.as_bytes();
+    let mut steps = Vec::new();
+    let mut i = 0;
+    if s.starts_with('$') {
+        i = 1;
+    } else if !s.starts_with('.') && !s.starts_with('[') {
+        // a bare field to start with, like `data` in `data.names`
+        i = field_end(s, 0);
+        if i == 0 {
+            return err(0, "Expected a field");
+        }
+        steps.push((Step::Field(s[..i].to_string()), i));
+    }
+    while i < bytes.len() {
+        match bytes[i] {
+            b'.' => {
+                let start = i + 1;
+                if s[start..].starts_with('*') {
+                    i = start + 1;
+                    steps.push((Step::Wildcard, i));
+                } else {
+                    i = field_end(s, start);
+                    if i == start {
+                        return err(start, "Expected a field");
+                    }
+                    steps.push((Step::Field(s[start..i].to_string()), i));
+                }
+            }
+            b'[' => {
+                let start = skip_spaces(s, i + 1);
+                let (step, close) = if s[start..].starts_with(['"', '\'']) {
+                    // a quoted field can have anything in it, `]` included
+                    let quote = &s[start..start + 1];
+                    let end = match s[start + 1..].find(quote) {
+                        Some(end) => start + 1 + end,
+                        None => return err(start, "Unclosed quote"),
+                    };
+                    let close = skip_spaces(s, end + 1);
+                    if !s[close..].starts_with(']') {
+                        return err(close, "Expected ']'");
+                    }
+                    (Step::Field(s[start + 1..end].to_string()), close)
+                } else {
+                    let close = match s[i..].find(']') {
+                        Some(close) => i + close,
+                        None => return err(i, "Unclosed '['"),
+                    };
+                    let inner = s[start..close].trim();
+                    let step = if inner == "*" {
+                        Step::Wildcard
+                    } else if let Ok(n) = inner.parse() {
+                        Step::Index(n)
+                    } else {
+                        return err(start, "Expected an index, '*' or a quoted field");
+                    };
+                    (step, close)
+                };
+                i = close + 1;
+                steps.push((step, i));
+            }
+            _ => return err(i, "Expected '.' or '['"),
+        }
+    }
+    Ok(steps)
+}
+
+fn skip_spaces(s: &str, start: usize) -> usize {
+    s.len() - s[start..].trim_start().len()
+}
+
+/// Where the first `c` in `s` is that isn't inside a quoted `['...']` field, so that paths can be
+/// separated by a character their field names may also contain.
+pub(crate) fn find_unquoted(s: &str, c: char) -> Option<usize> {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, x) in s.char_indices() {
+        match quote {
+            Some(q) if x == q => quote = None,
+            Some(_) => {}
+            None if (x == '"' || x == '\'') && prev == '[' => quote = Some(x),
+            None if x == c => return Some(i),
+            None => {}
+        }
+        if !x.is_whitespace() {
+            prev = x;
+        }
+    }
+    None
+}
+
+/// Where a field name starting at `start` ends.
+fn field_end(s: &str, start: usize) -> usize {
+    s[start..].find(['.', '[']).map_or(s.len(), |x| start + x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(path: &str, body: &str) -> Result<Vec<String>, String> {
+        let path: JsonPath = path.parse()?;
+        let data = json::parse(body).unwrap();
+        Ok(path.select(&data)?.iter().map(|x| x.dump()).collect())
+    }
+
+    #[test]
+    fn fields_and_indexes() {
+        let body = r#"{"data": {"names": ["pics", "aww"], "a.b": 1}}"#;
+        assert_eq!(
+            select("data.names", body),
+            Ok(vec![r#"["pics","aww"]"#.into()])
+        );
+        assert_eq!(select("$.data.names[1]", body), Ok(vec![r#""aww""#.into()]));
+        assert_eq!(select(r#"$["data"]['a.b']"#, body), Ok(vec!["1".into()]));
+        assert_eq!(
+            select(r#"$[ "data" ][ 'a.b' ]"#, body),
+            Ok(vec!["1".into()])
+        );
+        let odd_keys = r#"{"a]b": 1, "c[d": 2, "x'y": 3}"#;
+        assert_eq!(select("$['a]b']", odd_keys), Ok(vec!["1".into()]));
+        assert_eq!(select(r#"$["c[d"]"#, odd_keys), Ok(vec!["2".into()]));
+        assert_eq!(select(r#"$["x'y"]"#, odd_keys), Ok(vec!["3".into()]));
+        assert_eq!(select("$", body).unwrap().len(), 1);
+        assert_eq!(select("$.data.nope.names", body), Err("$.data.nope".into()));
+        assert_eq!(select("data.names[5]", body), Err("data.names[5]".into()));
+    }
+
+    #[test]
+    fn wildcards() {
+        let body = r#"{"data": {"children": [
+            {"data": {"display_name": "rust"}},
+            {"data": {}},
+            {"data": {"display_name": "rustjerk"}}
+        ]}}"#;
+        assert_eq!(
+            select("data.children[*].data.display_name", body),
+            Ok(vec![
+                r#""rust""#.into(),
+                "null".into(),
+                r#""rustjerk""#.into()
+            ])
+        );
+        assert_eq!(select("$.data.*", body).unwrap().len(), 1);
+        assert_eq!(select("$.data.children[*].nope[*]", body), Ok(vec![]));
+    }
+
+    #[test]
+    fn pointer() {
+        let body = r#"{"items": [{"a/b": "x"}, {"title": "y"}]}"#;
+        assert_eq!(select("/items/1/title", body), Ok(vec![r#""y""#.into()]));
+        assert_eq!(select("/items/0/a~1b", body), Ok(vec![r#""x""#.into()]));
+        assert_eq!(select("/items/2", body), Err("/items/2".into()));
+    }
+
+    #[test]
+    fn bad_syntax() {
+        assert!("$.".parse::<JsonPath>().is_err());
+        assert!("$[1".parse::<JsonPath>().is_err());
+        assert!("$[foo]".parse::<JsonPath>().is_err());
+        assert!("$['a".parse::<JsonPath>().is_err());
+        assert!("$['a'b]".parse::<JsonPath>().is_err());
+        assert!("$.a b[0]c".parse::<JsonPath>().is_err());
+        assert!("".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn unquoted() {
+        assert_eq!(find_unquoted("$.a|$.b", '|'), Some(3));
+        assert_eq!(find_unquoted("$['a|b']|$.b", '|'), Some(8));
+        assert_eq!(find_unquoted(r#"$[ "a|b" ]"#, '|'), None);
+    }
+}
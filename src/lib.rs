@@ -0,0 +1,96 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+//! Engine resolution, URL formatting and suggestion fetching behind the `sefr` terminal program.
+//!
+//! The binary is a thin UI on top of this crate, so other launchers can reuse the same config
+//! and engines.
+//!
+//! ```
+//! let config = sefr::parse_config(r#"
+//!     [engines._default]
+//!     name = "Google"
+//!     search_url = "https://www.google.com/search?q=%s"
+//!
+//!     [engines.w]
+//!     name = "Wikipedia"
+//!     search_url = "https://en.wikipedia.org/wiki/%s"
+//!     space_becomes = "_"
+//!
+//!     [keybinds]
+//! "#).unwrap();
+//!
+//! let (engine, prefix, search_term) = sefr::match_engine("w rust ownership", &config.engines);
+//! assert_eq!(prefix, "w");
+//! assert_eq!(
+//!     engine.format_search_url(&search_term),
+//!     "https://en.wikipedia.org/wiki/rust_ownership"
+//! );
+//! ```
+
+extern crate base64;
+extern crate crossterm;
+extern crate directories;
+extern crate json;
+#[cfg(unix)]
+extern crate libc;
+extern crate regex;
+extern crate roxmltree;
+extern crate serde;
+extern crate shell_words;
+extern crate toml;
+extern crate ureq;
+extern crate webbrowser;
+
+use crossterm::{Attribute, Color, Colored, KeyEvent};
+
+use std::cmp::min;
+use std::collections::HashMap;
+use std::result::Result;
+
+pub mod cache;
+pub mod config;
+pub mod engine;
+pub mod history;
+pub mod http;
+pub mod json_path;
+pub mod opener;
+pub mod suggestion_adapter;
+pub mod suggestion_source;
+pub mod template;
+pub mod util;
+pub mod xml_path;
+
+use util::*;
+
+pub use cache::{fetch_cached, CacheKey, SuggestionCache};
+pub use config::{
+    get_config, load_config, parse_config, BindableAction, Config, ConfigError, KeyBind, OpenWith,
+    SuggestionAdapterName, SuggestionSettings,
+};
+pub use engine::{detect_url, match_engine, match_engine_or_locked, Engine, Prompt};
+pub use history::History;
+pub use http::{http_get, HttpSettings};
+pub use json_path::JsonPath;
+pub use opener::{open_url, run_command};
+pub use suggestion_adapter::{
+    fetch_suggestions, register_adapter, AdapterConstructor, CustomAdapter, LinePattern,
+    SuggestionAdapter, SuggestionError, Suggestions,
+};
+pub use suggestion_source::{fetch_from_source, merge_suggestions, SourceKind, SuggestionSource};
+pub use template::{split_params, Template, QUERY_COMPONENT_ENCODE_SET};
+pub use xml_path::XmlPath;
@@ -0,0 +1,327 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+/// What kind of edit last changed the buffer, so that runs of the same kind can be undone at once.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditKind {
+    /// Typing a word.
+    Insert,
+    /// Cycling through suggestions.
+    Select,
+    /// Anything else. Never merged with the previous edit.
+    Other,
+}
+
+/// The input buffer, with a cursor, a readline-style kill buffer and undo history.
+#[derive(Debug, Default)]
+pub struct LineEditor {
+    text: String,
+    /// Byte index into `text`, always on a char boundary.
+    cursor: usize,
+    kill_buffer: String,
+    /// `(text, cursor)` snapshots from before each edit.
+    undo_stack: Vec<(String, usize)>,
+    redo_stack: Vec<(String, usize)>,
+    last_edit: Option<EditKind>,
+}
+
+impl LineEditor {
+    pub fn new(text: String) -> LineEditor {
+        LineEditor {
+            cursor: text.len(),
+            text,
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The cursor position in chars, for rendering.
+    pub fn cursor_col(&self) -> usize {
+        self.text[..self.cursor].chars().count()
+    }
+
+    /// Replaces the whole buffer with a selected suggestion and puts the cursor at the end.
+    /// Cycling through several suggestions in a row is undone in one step.
+    pub fn select(&mut self, text: String) {
+        self.edit(EditKind::Select, |ed| {
+            ed.cursor = text.len();
+            ed.text = text;
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.edit(EditKind::Other, |ed| {
+            ed.text.clear();
+            ed.cursor = 0;
+        });
+    }
+
+    pub fn insert(&mut self, c: char) {
+        // undo a word at a time
+        let kind = if c.is_whitespace() {
+            EditKind::Other
+        } else {
+            EditKind::Insert
+        };
+        self.edit(kind, |ed| {
+            ed.text.insert(ed.cursor, c);
+            ed.cursor += c.len_utf8();
+        });
+    }
+
+    pub fn move_left(&mut self) {
+        self.move_to(self.prev_char_boundary());
+    }
+
+    pub fn move_right(&mut self) {
+        self.move_to(self.next_char_boundary());
+    }
+
+    pub fn home(&mut self) {
+        self.move_to(0);
+    }
+
+    pub fn end(&mut self) {
+        self.move_to(self.text.len());
+    }
+
+    pub fn word_left(&mut self) {
+        self.move_to(self.prev_word_boundary());
+    }
+
+    pub fn word_right(&mut self) {
+        // skip whitespace, then the word itself
+        let rest = &self.text[self.cursor..];
+        let word_start = rest.len() - rest.trim_start().len();
+        let word_len = rest[word_start..]
+            .find(char::is_whitespace)
+            .unwrap_or(rest.len() - word_start);
+        self.move_to(self.cursor + word_start + word_len);
+    }
+
+    /// Deletes the char before the cursor (backspace).
+    pub fn delete_back(&mut self) {
+        let start = self.prev_char_boundary();
+        self.edit(EditKind::Other, |ed| {
+            ed.text.replace_range(start..ed.cursor, "");
+            ed.cursor = start;
+        });
+    }
+
+    /// Deletes the char under the cursor.
+    pub fn delete_forward(&mut self) {
+        let end = self.next_char_boundary();
+        self.edit(EditKind::Other, |ed| {
+            ed.text.replace_range(ed.cursor..end, "");
+        });
+    }
+
+    /// Deletes the word before the cursor, along with any whitespace between it and the cursor.
+    pub fn delete_word(&mut self) {
+        let start = self.prev_word_boundary();
+        self.kill(start, self.cursor);
+    }
+
+    pub fn kill_to_end(&mut self) {
+        self.kill(self.cursor, self.text.len());
+    }
+
+    pub fn kill_to_start(&mut self) {
+        self.kill(0, self.cursor);
+    }
+
+    /// Inserts the last killed text at the cursor.
+    pub fn yank(&mut self) {
+        let killed = self.kill_buffer.clone();
+        self.edit(EditKind::Other, |ed| {
+            ed.text.insert_str(ed.cursor, &killed);
+            ed.cursor += killed.len();
+        });
+    }
+
+    /// Restores the buffer to how it was before the last edit.
+    pub fn undo(&mut self) {
+        if let Some(state) = self.undo_stack.pop() {
+            let current = self.restore(state);
+            self.redo_stack.push(current);
+        }
+    }
+
+    /// Reapplies the last undone edit.
+    pub fn redo(&mut self) {
+        if let Some(state) = self.redo_stack.pop() {
+            let current = self.restore(state);
+            self.undo_stack.push(current);
+        }
+    }
+
+    /// Runs `f` on the buffer, recording an undo step if it changed the text. Consecutive edits
+    /// of the same kind (other than `Other`) share a step.
+    fn edit<F: FnOnce(&mut LineEditor)>(&mut self, kind: EditKind, f: F) {
+        let before = (self.text.clone(), self.cursor);
+        f(self);
+        if self.text == before.0 {
+            return;
+        }
+        if kind == EditKind::Other || self.last_edit != Some(kind) {
+            self.undo_stack.push(before);
+        }
+        self.redo_stack.clear();
+        self.last_edit = Some(kind);
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor;
+        // typing somewhere else starts a new undo step
+        self.last_edit = None;
+    }
+
+    fn restore(&mut self, (text, cursor): (String, usize)) -> (String, usize) {
+        self.last_edit = None;
+        let current = (std::mem::replace(&mut self.text, text), self.cursor);
+        self.cursor = cursor;
+        current
+    }
+
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        self.kill_buffer = self.text[start..end].to_string();
+        self.edit(EditKind::Other, |ed| {
+            ed.text.replace_range(start..end, "");
+            ed.cursor = start;
+        });
+    }
+
+    fn prev_char_boundary(&self) -> usize {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_char_boundary(&self) -> usize {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map_or(self.cursor, |c| self.cursor + c.len_utf8())
+    }
+
+    fn prev_word_boundary(&self) -> usize {
+        // trim, then go backwards until the first whitespace or the beginning of the line
+        self.text[..self.cursor]
+            .trim_end()
+            .trim_end_matches(|x: char| !x.is_whitespace())
+            .len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_move() {
+        let mut ed = LineEditor::new("héllo".into());
+        assert_eq!(ed.cursor_col(), 5);
+        ed.move_left();
+        ed.move_left();
+        ed.move_left();
+        ed.move_left();
+        ed.insert('x');
+        assert_eq!(ed.text(), "hxéllo");
+        ed.delete_forward();
+        assert_eq!(ed.text(), "hxllo");
+        ed.delete_back();
+        ed.delete_back();
+        ed.delete_back();
+        assert_eq!(ed.text(), "llo");
+        assert_eq!(ed.cursor_col(), 0);
+        ed.end();
+        ed.move_right();
+        assert_eq!(ed.cursor_col(), 3);
+    }
+
+    #[test]
+    fn words() {
+        let mut ed = LineEditor::new("yt  never gonna".into());
+        ed.word_left();
+        assert_eq!(ed.cursor_col(), 10);
+        ed.word_left();
+        ed.word_left();
+        assert_eq!(ed.cursor_col(), 0);
+        ed.word_right();
+        assert_eq!(ed.cursor_col(), 2);
+        ed.word_right();
+        assert_eq!(ed.cursor_col(), 9);
+        ed.delete_word();
+        assert_eq!(ed.text(), "yt   gonna");
+    }
+
+    #[test]
+    fn kill_and_yank() {
+        let mut ed = LineEditor::new("never gonna give".into());
+        ed.word_left();
+        ed.kill_to_end();
+        assert_eq!(ed.text(), "never gonna ");
+        ed.home();
+        ed.yank();
+        assert_eq!(ed.text(), "givenever gonna ");
+        ed.kill_to_start();
+        assert_eq!(ed.text(), "never gonna ");
+        ed.end();
+        ed.yank();
+        assert_eq!(ed.text(), "never gonna give");
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut ed = LineEditor::new(String::new());
+        for c in "yt never".chars() {
+            ed.insert(c);
+        }
+        ed.select("yt never gonna give you up".into());
+        ed.select("yt never gonna let you down".into());
+        ed.clear();
+        assert_eq!(ed.text(), "");
+
+        ed.undo();
+        assert_eq!(ed.text(), "yt never gonna let you down");
+        ed.undo();
+        assert_eq!(ed.text(), "yt never");
+        assert_eq!(ed.cursor_col(), 8);
+        ed.undo();
+        assert_eq!(ed.text(), "yt ");
+        ed.redo();
+        assert_eq!(ed.text(), "yt never");
+        ed.redo();
+        ed.redo();
+        assert_eq!(ed.text(), "");
+        ed.redo();
+        assert_eq!(ed.text(), "");
+
+        // a new edit drops whatever could have been redone
+        ed.undo();
+        ed.insert('!');
+        ed.redo();
+        assert_eq!(ed.text(), "yt never gonna let you down!");
+    }
+}
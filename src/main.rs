@@ -15,40 +15,198 @@
 // * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
 // *************************************************************************
 
-extern crate crossterm;
-extern crate directories;
-extern crate json;
-extern crate minreq;
-extern crate serde;
-extern crate toml;
-extern crate webbrowser;
-
 use crossterm::{
     input, Attribute, ClearType, Color, Colored, Crossterm, InputEvent, KeyEvent, RawScreen,
+    Terminal, TerminalCursor,
 };
 
 use std::cmp::min;
-use std::collections::HashMap;
-use std::result::Result;
-use std::sync::mpsc;
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-mod config;
-mod engine;
-mod suggestion_adapter;
-mod util;
+mod cli;
+mod fetcher;
+mod line_editor;
 
-use config::*;
-use engine::*;
-use suggestion_adapter::*;
-use util::*;
+use cli::*;
+use fetcher::{Fetcher, Job};
+use line_editor::LineEditor;
+use sefr::util::*;
+use sefr::*;
 
-#[allow(unused_must_use)]
 fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Command::Run(args)) => args,
+        Ok(Command::Help) => {
+            println!("{}", usage());
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("{}", version());
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, usage());
+            process::exit(2);
+        }
+    };
+
+    let config = get_config(args.config_path.as_deref());
+    let locked_engine = match args.engine {
+        // the default engine is stored under an empty prefix once the config is validated
+        Some(ref prefix) if prefix == "_default" => Some(String::new()),
+        Some(prefix) => {
+            if !config.engines.contains_key(&prefix) {
+                eprintln!("No engine with prefix '{}' found.", prefix);
+                process::exit(2);
+            }
+            Some(prefix)
+        }
+        None => None,
+    };
+    let mut initial_input = args.initial_input;
+    // `sefr yt` should select the engine right away instead of searching for "yt"
+    if locked_engine.is_none()
+        && !initial_input.is_empty()
+        && config.engines.contains_key(&initial_input)
+    {
+        initial_input.push(' ');
+    }
+    match args.mode {
+        Mode::Interactive => {
+            // the UI has to be torn down before opening, so that errors and commands that
+            // print something aren't mangled by raw mode
+            match run_ui(config, locked_engine, initial_input) {
+                Some(Submission::Url(url, open_with)) => {
+                    if let Err(e) = open_url(&url, &open_with) {
+                        print_error(&format!("Couldn't open {} with {}: {}", url, open_with, e));
+                        process::exit(1);
+                    }
+                }
+                Some(Submission::Command(command, terminal)) => {
+                    if let Err(e) = run_command(&command, terminal) {
+                        let command = shell_words::join(&command);
+                        print_error(&format!("Couldn't run {}: {}", command, e));
+                        process::exit(1);
+                    }
+                }
+                None => {}
+            }
+        }
+        Mode::PrintUrl => {
+            let (engine, prefix, search_term) =
+                match_engine_or_locked(&initial_input, &config.engines, locked_engine.as_deref());
+            if let Some(url) = typed_url(&initial_input, &prefix, locked_engine.is_some()) {
+                println!("{}", url);
+            } else if engine.command.is_empty() {
+                println!("{}", engine.format_search_url(&search_term));
+            } else {
+                println!("{}", shell_words::join(engine.format_command(&search_term)));
+            }
+        }
+        Mode::Suggest => {
+            let (engine, prefix, search_term) =
+                match_engine_or_locked(&initial_input, &config.engines, locked_engine.as_deref());
+            if search_term.is_empty() {
+                return;
+            }
+            let history = if config.history {
+                History::load().ok()
+            } else {
+                None
+            };
+            let sources = engine.sources(history.is_some());
+            let cache = Mutex::new(load_cache(&config.suggestions));
+            let (prefix, history, search_term) = (&prefix, history.as_ref(), &search_term);
+            let cache = &cache;
+            let results: Vec<_> = thread::scope(|scope| {
+                let fetches: Vec<_> = sources
+                    .iter()
+                    .map(|source| {
+                        scope.spawn(move || {
+                            fetch_cached(cache, engine, prefix, source, history, search_term)
+                        })
+                    })
+                    .collect();
+                fetches.into_iter().map(|x| x.join().unwrap()).collect()
+            });
+            let mut failed = false;
+            for (source, result) in sources.iter().zip(&results) {
+                if let Err(e) = result {
+                    eprintln!(
+                        "Could not get suggestions from {}: {}",
+                        source_name(engine, source),
+                        e
+                    );
+                    failed = true;
+                }
+            }
+            if let Err(e) = cache.lock().unwrap().save() {
+                eprintln!("Couldn't save suggestion cache: {}", e);
+            }
+            let results: Vec<_> = results.into_iter().map(Result::ok).collect();
+            let sugg_terms = merge_suggestions(&results)
+                .map(|x| x.sugg_terms)
+                .unwrap_or_default();
+            if args.json {
+                let mut out = json::JsonValue::new_object();
+                out["term"] = search_term.as_str().into();
+                out["suggestions"] = sugg_terms.into();
+                println!("{}", out.dump());
+            } else {
+                for sugg in sugg_terms {
+                    println!("{}", sugg);
+                }
+            }
+            if failed {
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn print_error(message: &str) {
+    eprintln!(
+        "{}{}Error!{} {}",
+        Attribute::Bold,
+        Colored::Fg(Color::Red),
+        Attribute::Reset,
+        message
+    );
+}
+
+/// What to do once the UI is gone.
+enum Submission {
+    /// Open a URL.
+    Url(String, OpenWith),
+    /// Run an engine's command, in the terminal or not.
+    Command(Vec<String>, bool),
+}
+
+/// The URL to open instead of searching, if what was typed for the default engine looks like one.
+fn typed_url(input_line: &str, prefix: &str, locked: bool) -> Option<String> {
+    if locked || !prefix.is_empty() {
+        return None;
+    }
+    detect_url(input_line)
+}
+
+/// Runs the interactive UI until the user submits or exits. On submit, returns what to do with the
+/// search.
+#[allow(unused_must_use)]
+fn run_ui(
+    mut config: Config,
+    locked_engine: Option<String>,
+    initial_input: String,
+) -> Option<Submission> {
     let (tx, rx) = mpsc::channel();
 
-    let config = get_config();
-    let engines = config.engines;
+    let engines = &config.engines;
     let _screen = RawScreen::into_raw_mode();
     let crossterm = Crossterm::new();
     let mut cursor = crossterm.cursor();
@@ -56,9 +214,8 @@ fn main() {
 
     let input = input();
     let mut stdin = input.read_sync();
-    let mut input_buf = String::new();
 
-    let keybindings = config.keybinds;
+    let keybindings = std::mem::take(&mut config.keybinds);
     let input_tx = tx.clone();
     let input_thread = thread::spawn(move || loop {
         let key = stdin.next();
@@ -88,151 +245,295 @@ fn main() {
         }
     });
 
-    cursor.hide();
-    let mut input_line: String = String::from("");
-    let mut suggs: Option<Suggestions> = None;
+    // a broken history file shouldn't keep the UI from starting, so just start without it
+    let mut history = if config.history {
+        History::load().ok()
+    } else {
+        None
+    };
+
+    let mut editor = LineEditor::new(initial_input);
     let mut prompt = &engines.get("").expect("No default engine set.").prompt;
-    let mut waiting_for_term: Option<String> = None; // the term for which we are expecting suggestions (in case of out-of-order resolves)
     let mut selected_n: Option<usize> = None;
 
-    let mut prev_engine: Option<&Engine> = None;
+    // the current engine's sources, and the latest suggestions and errors from each of them
+    let mut sources: Vec<SuggestionSource> = Vec::new();
+    let mut source_results: Vec<Option<Suggestions>> = Vec::new();
+    let mut source_errors: Vec<Option<SuggestionError>> = Vec::new();
+    // bumped whenever the search term changes, so that out-of-order responses can be ignored
+    let mut generation: u64 = 0;
+    let cache = Arc::new(Mutex::new(load_cache(&config.suggestions)));
+    let fetcher = Fetcher::new(config.suggestions.max_workers, cache.clone(), tx.clone());
+    let debounce = Duration::from_millis(config.suggestions.debounce_ms);
+    // when to ask the sources that aren't history or cached, once the user stops typing for a bit
+    let mut fetch_at: Option<Instant> = None;
+    let mut to_fetch: Vec<usize> = Vec::new();
+
+    let mut prev_prefix: Option<String> = None;
     let mut refresh_completions = true;
+    // what suggestions were last asked for, so keys that don't change it leave them alone
+    let mut prev_query: Option<(String, String)> = None;
+    // set when selecting a suggestion changes the input line, which shouldn't replace the list
+    let mut keep_suggestions = false;
 
     let mut t_w: u16;
+    let mut submitted = None;
+    let mut history_error = None;
     // main UI loop
     loop {
         t_w = terminal.terminal_size().0; // refresh terminal width in case it was resized
-        let (engine, prefix, search_term) = match_engine(&input_line, &engines);
-        if let Some(ref prev_engine) = prev_engine {
-            // if the engine has changed (based on suggestion url)
-            if prev_engine.suggestion_url != engine.suggestion_url {
-                suggs = None; // clear the list that gets drawn asap
-                refresh_completions = true; // and force an update
-            }
+        let (engine, prefix, search_term) =
+            match_engine_or_locked(editor.text(), engines, locked_engine.as_deref());
+        if prev_prefix.as_ref() != Some(&prefix) {
+            // the engine has changed, so clear the list that gets drawn asap and force an update
+            sources = engine.sources(history.is_some());
+            source_results = vec![None; sources.len()];
+            source_errors = vec![None; sources.len()];
+            refresh_completions = true;
+            prev_prefix = Some(prefix.clone());
         }
-        prev_engine = Some(&engine);
+        let query = Some((prefix.clone(), search_term.clone()));
+        if query != prev_query {
+            refresh_completions |= !keep_suggestions;
+            prev_query = query;
+        }
+        keep_suggestions = false;
         if refresh_completions {
             prompt = &engine.prompt;
+            generation += 1;
+            // whatever is still queued or running is for an old search term now
+            fetcher.submit(generation, Vec::new());
+            fetch_at = None;
+            to_fetch.clear();
             if search_term.is_empty() {
-                suggs = None;
-                waiting_for_term = None;
+                source_results = vec![None; sources.len()];
+                source_errors = vec![None; sources.len()];
             } else {
-                waiting_for_term = Some(search_term.clone());
-                let url = engine.format_suggestion_url(&search_term);
-                let tx2 = tx.clone();
-                // TODO: this should be done with boxed traits I think?
-                let sugg_adapter = engine.suggestion_adapter.clone();
-                let search_term2 = search_term.clone();
-                // spawn a separate thread to do the http request and send the result to the
-                // channel that this thread is receiving on
-                if !url.is_empty() {
-                    thread::spawn(move || {
-                        let resolved_suggs = match sugg_adapter {
-                            SuggestionAdapterName::OpenSearch => {
-                                OpenSearchAdapter::get(url, search_term2)
-                            }
-                            SuggestionAdapterName::JsonPath(path) => {
-                                JsonPathAdapter(path).get(url, search_term2)
-                            }
-                        };
-                        match resolved_suggs{
-                            Ok(resolved_suggs) => {
-                                tx2.send(UiMsg::SetSuggestions(resolved_suggs));
-                            }
-                            Err(e) => {
-                                // hacky but i want the error to be seen
-                                for _ in 1..20 {
-                                    println!("Error: {}", e);
-                                }
-                            }
-                        }
-                    });
+                for (i, source) in sources.iter().enumerate() {
+                    if source.is_history() {
+                        // quick enough to do right here
+                        source_results[i] = fetch_from_source(
+                            engine,
+                            &prefix,
+                            source,
+                            history.as_ref(),
+                            &search_term,
+                        )
+                        .ok();
+                        continue;
+                    }
+                    let cached = source
+                        .cache_key(engine, &search_term)
+                        .and_then(|key| cache.lock().unwrap().get(&key).cloned());
+                    if cached.is_some() {
+                        source_results[i] = cached;
+                        source_errors[i] = None;
+                    } else {
+                        to_fetch.push(i);
+                    }
+                }
+                if !to_fetch.is_empty() {
+                    fetch_at = Some(Instant::now() + debounce);
                 }
             }
             refresh_completions = false;
             selected_n = None;
         }
+        let suggs = merge_suggestions(&source_results);
+        // shown in a status line below the prompt until the source answers again
+        let suggestion_error = sources
+            .iter()
+            .zip(&source_errors)
+            .find_map(|(source, error)| Some((source, error.as_ref()?)))
+            .map(|(source, error)| format!("{}: {}", source_name(engine, source), error));
+        cursor.hide(); // so it doesn't jump around while redrawing
         cursor.move_left(t_w);
         terminal.clear(ClearType::CurrentLine);
 
-        let full_prompt_line = format!("{} {}_", prompt, input_line);
-        if full_prompt_line.len() >= t_w as usize {
-            let short_prompt = format!("{}", prompt.to_short());
-            print!("{}", short_prompt);
+        // the column the cursor goes to once everything is drawn
+        let cursor_x = if prompt.width() + 1 + editor.text().chars().count() + 1 >= t_w as usize {
+            let short_prompt = prompt.to_short();
             // 2 = spacer + cursor
-            // TODO: don't count control characters for length
-            let room_for_input_line = (t_w as usize)
-                .checked_sub(short_prompt.len() + 2)
-                .unwrap_or(0);
-            let truncated_input_line = truncate_from_end(&input_line, room_for_input_line);
-            println!(" {}_", truncated_input_line);
+            let room_for_input_line = (t_w as usize).saturating_sub(short_prompt.width() + 2);
+            let (truncated_input_line, cursor_col) =
+                truncate_around_cursor(editor.text(), editor.cursor_col(), room_for_input_line);
+            println!("{} {}", short_prompt, truncated_input_line);
+            short_prompt.width() + 1 + cursor_col
         } else {
-            // just printing full_prompt_line doesn't preserve colours for some reason
-            println!("{} {}_", prompt, input_line);
-        }
+            println!("{} {}", prompt, editor.text());
+            prompt.width() + 1 + editor.cursor_col()
+        };
 
         let suggest_lines = 15; /*if let Some(ref suggs) = suggs {
                                     suggs.sugg_terms.len()
                                 } else {
                                     0
                                 }; */
+        // the status line takes the place of the first suggestion
+        let sugg_rows = if suggestion_error.is_some() {
+            suggest_lines - 1
+        } else {
+            suggest_lines
+        };
         let selectable_lines = if let Some(ref suggs) = suggs {
-            min(suggest_lines, suggs.sugg_terms.len())
+            min(sugg_rows, suggs.sugg_terms.len())
         } else {
             0
         };
-        for n in 0..suggest_lines {
+        if let Some(ref error) = suggestion_error {
+            terminal.clear(ClearType::CurrentLine);
+            cursor.move_left(t_w);
+            println!(
+                "{}{}{}",
+                Colored::Fg(Color::Red),
+                truncate_from_end(error, t_w as usize),
+                Attribute::Reset
+            );
+        }
+        for n in 0..sugg_rows {
             terminal.clear(ClearType::CurrentLine);
             cursor.move_left(t_w);
 
             if let Some(ref suggs) = suggs {
-                match suggs.sugg_terms.get(n) {
-                    Some(line) => {
-                        let line_trunc = truncate_from_end(&line, t_w as usize);
-                        match selected_n {
-                            Some(selected_n) if selected_n == n => {
-                                print!(
-                                    "{}{}{}{}",
-                                    Colored::Fg(Color::Black),
-                                    Colored::Bg(Color::White),
-                                    line_trunc,
-                                    Attribute::Reset
-                                );
-                            }
-                            _ => {
-                                print!("{}", line_trunc);
+                if let Some(line) = suggs.sugg_terms.get(n) {
+                    let line_trunc = truncate_from_end(line, t_w as usize);
+                    let source = suggs.source(n).and_then(|x| sources.get(x));
+                    let label = source.map_or("", |x| x.label.as_str());
+                    let mut room_after_line =
+                        (t_w as usize).saturating_sub(line_trunc.chars().count());
+                    match selected_n {
+                        Some(selected_n) if selected_n == n => {
+                            print!(
+                                "{}{}{}{}",
+                                Colored::Fg(Color::Black),
+                                Colored::Bg(Color::White),
+                                line_trunc,
+                                Attribute::Reset
+                            );
+                        }
+                        _ => match source.and_then(|x| x.color) {
+                            Some(color) => {
+                                print!("{}{}{}", Colored::Fg(color), line_trunc, Attribute::Reset)
                             }
+                            None => print!("{}", line_trunc),
+                        },
+                    }
+                    // 3 = spacer + brackets
+                    if !label.is_empty() && room_after_line >= label.chars().count() + 3 {
+                        print!(" {}[{}]{}", Attribute::Dim, label, Attribute::Reset);
+                        room_after_line -= label.chars().count() + 3;
+                    }
+                    if let Some(description) = suggs.description(n) {
+                        // 2 = spacer between the suggestion and its description
+                        if room_after_line > 2 {
+                            let description = truncate_end(description, room_after_line - 2);
+                            print!("  {}{}{}", Attribute::Dim, description, Attribute::Reset);
                         }
                     }
-                    None => {} // outside sugg bounds
                 }
             }
 
             println!();
         }
-        let msg = rx.recv().unwrap();
+        // put the real cursor back into the input line while we wait
+        cursor.move_up(suggest_lines as u16 + 1);
+        if cursor_x > 0 {
+            cursor.move_right(cursor_x as u16);
+        }
+        cursor.show();
+        io::stdout().flush();
+
+        let msg = match fetch_at {
+            Some(at) => match rx.recv_timeout(at.saturating_duration_since(Instant::now())) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => {
+                    // nothing typed for a while, so the workers can go ahead
+                    let jobs = to_fetch
+                        .drain(..)
+                        .map(|i| Job {
+                            engine: engine.clone(),
+                            prefix: prefix.clone(),
+                            source_index: i,
+                            source: sources[i].clone(),
+                            search_term: search_term.clone(),
+                        })
+                        .collect();
+                    fetcher.submit(generation, jobs);
+                    fetch_at = None;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => unreachable!(),
+            },
+            None => rx.recv().unwrap(),
+        };
         match msg {
-            UiMsg::SetSuggestions(suggestion_update) => {
-                if let Some(ref expected_term) = waiting_for_term {
-                    if &suggestion_update.term == expected_term {
-                        suggs = Some(suggestion_update);
+            UiMsg::SetSuggestions {
+                generation: for_generation,
+                source,
+                result,
+            } => {
+                // ignore responses for what's no longer in the input line
+                if for_generation == generation {
+                    // the selection follows its suggestion around as the list changes, or goes
+                    // away if it isn't there anymore, so Submit can't open another one's URL
+                    let selected = match (&suggs, selected_n) {
+                        (Some(suggs), Some(n)) => Some((
+                            suggs.sugg_terms.get(n).cloned(),
+                            suggs.url(n).map(str::to_string),
+                        )),
+                        _ => None,
+                    };
+                    match result {
+                        Ok(suggs) => {
+                            source_results[source] = Some(suggs);
+                            source_errors[source] = None;
+                        }
+                        Err(e) => source_errors[source] = Some(e),
+                    }
+                    if let Some((term, url)) = selected {
+                        let merged = merge_suggestions(&source_results);
+                        selected_n = merged.and_then(|merged| {
+                            (0..merged.sugg_terms.len()).find(|&n| {
+                                merged.sugg_terms.get(n) == term.as_ref()
+                                    && merged.url(n) == url.as_deref()
+                            })
+                        });
                     }
                 }
             }
             UiMsg::OnInput(key) => {
-                refresh_completions = true;
                 match key {
                     BindableAction::Submit => {
-                        input_buf.clear();
-                        let url = engine.format_search_url(&search_term);
-                        for _ in 0..suggest_lines {
-                            cursor.move_up(1);
-                            terminal.clear(ClearType::CurrentLine);
+                        if let Some(ref mut history) = history {
+                            history.record(&prefix, &search_term);
+                            if let Err(e) = history.save() {
+                                history_error = Some(e);
+                            }
+                        }
+                        // a suggestion that comes with its own URL goes straight there
+                        let url = match (&suggs, selected_n) {
+                            (Some(suggs), Some(n)) => suggs.url(n).map(str::to_string),
+                            _ => None,
                         }
-                        cursor.move_left(t_w);
-                        println!("Opening {}", url);
+                        .or_else(|| typed_url(editor.text(), &prefix, locked_engine.is_some()));
+                        clear_below_prompt(&mut cursor, &terminal, t_w);
+                        submitted = Some(match url {
+                            None if !engine.command.is_empty() => {
+                                let command = engine.format_command(&search_term);
+                                println!("Running {}", shell_words::join(&command));
+                                Submission::Command(command, engine.terminal)
+                            }
+                            url => {
+                                let url =
+                                    url.unwrap_or_else(|| engine.format_search_url(&search_term));
+                                let open_with = config.open_with_for(engine);
+                                if *open_with != OpenWith::Print {
+                                    println!("Opening {}", url);
+                                }
+                                Submission::Url(url, open_with.clone())
+                            }
+                        });
                         terminal.clear(ClearType::CurrentLine);
-                        webbrowser::open(&url).expect("Couldn't open browser.");
                         break;
                     }
                     BindableAction::SelectNext => {
@@ -246,13 +547,19 @@ fn main() {
                                 selected_n = Some(0);
                             }
                             if let Some(selected) = suggs.sugg_terms.get(selected_n.unwrap()) {
-                                let (_, interfering_prefix, _) = match_engine(&selected, &engines);
-                                input_line = input_line_from_selection(
-                                    &interfering_prefix,
-                                    &prefix,
-                                    &selected,
-                                );
-                                refresh_completions = false;
+                                editor.select(if locked_engine.is_some() {
+                                    // no prefix in the input line, so nothing can interfere
+                                    selected.clone()
+                                } else {
+                                    let (_, interfering_prefix, _) =
+                                        match_engine(selected, engines);
+                                    input_line_from_selection(
+                                        &interfering_prefix,
+                                        &prefix,
+                                        selected,
+                                    )
+                                });
+                                keep_suggestions = true;
                             }
                         }
                     }
@@ -265,55 +572,97 @@ fn main() {
                                     .unwrap_or(selectable_lines.checked_sub(1).unwrap_or(0)),
                             );
                             if let Some(selected) = suggs.sugg_terms.get(selected_n.unwrap()) {
-                                let (_, interfering_prefix, _) = match_engine(&selected, &engines);
-                                input_line = input_line_from_selection(
-                                    &interfering_prefix,
-                                    &prefix,
-                                    &selected,
-                                );
-                                refresh_completions = false;
+                                editor.select(if locked_engine.is_some() {
+                                    // no prefix in the input line, so nothing can interfere
+                                    selected.clone()
+                                } else {
+                                    let (_, interfering_prefix, _) =
+                                        match_engine(selected, engines);
+                                    input_line_from_selection(
+                                        &interfering_prefix,
+                                        &prefix,
+                                        selected,
+                                    )
+                                });
+                                keep_suggestions = true;
                             }
                         }
                     }
 
                     BindableAction::DeleteChar => {
-                        input_line.pop();
+                        editor.delete_back();
                         selected_n = None;
                     }
                     BindableAction::Exit => {
-                        terminal.clear(ClearType::CurrentLine);
-                        for _ in 0..suggest_lines {
-                            cursor.move_up(1);
-                            terminal.clear(ClearType::CurrentLine);
-                        }
+                        clear_below_prompt(&mut cursor, &terminal, t_w);
                         break;
                     }
-                    BindableAction::DeleteWord => {
-                        // delete last word (trim, then delete backwards until first
-                        // word character or beginning of line
-                        input_line = input_line
-                            .trim_end()
-                            .trim_end_matches(|x: char| !x.is_whitespace())
-                            .to_string();
-                    }
+                    BindableAction::DeleteWord => editor.delete_word(),
+                    BindableAction::DeleteForward => editor.delete_forward(),
+                    BindableAction::KillToEnd => editor.kill_to_end(),
+                    BindableAction::KillToStart => editor.kill_to_start(),
+                    BindableAction::Yank => editor.yank(),
+                    BindableAction::ClearInput => editor.clear(),
+                    BindableAction::Undo => editor.undo(),
+                    BindableAction::Redo => editor.redo(),
                     BindableAction::AddChar(character) => {
                         // if we don't want spaces in the search term, like with subreddits
                         // just make space only select a suggestion if one is highlighted
                         if !(character == ' ' && engine.space_becomes.is_empty()) {
-                            input_line.push(character as char);
+                            editor.insert(character);
                         }
                         selected_n = None;
                     }
-                    _ => {}
+                    BindableAction::MoveLeft => editor.move_left(),
+                    BindableAction::MoveRight => editor.move_right(),
+                    BindableAction::WordLeft => editor.word_left(),
+                    BindableAction::WordRight => editor.word_right(),
+                    BindableAction::Home => editor.home(),
+                    BindableAction::End => editor.end(),
                 }
             }
         };
-        cursor.move_up(suggest_lines as u16 + 1);
     }
     cursor.show();
     cursor.move_left(t_w);
 
     input_thread.join();
+    if let Some(e) = history_error {
+        eprintln!("Couldn't save history: {}", e);
+    }
+    if let Err(e) = cache.lock().unwrap().save() {
+        eprintln!("Couldn't save suggestion cache: {}", e);
+    }
+    submitted
+}
+
+/// The suggestion cache, loaded from disk if `disk_cache` is on. A broken cache file just means
+/// starting over, but one that can't be read at all means not using the disk this time.
+fn load_cache(settings: &SuggestionSettings) -> SuggestionCache {
+    if settings.disk_cache {
+        if let Ok(cache) = SuggestionCache::load(settings.cache_ttl_secs) {
+            return cache;
+        }
+    }
+    SuggestionCache::new(settings.cache_ttl_secs)
+}
+
+/// What to call a source in error messages.
+fn source_name(engine: &Engine, source: &SuggestionSource) -> String {
+    if source.label.is_empty() {
+        engine.name.clone()
+    } else {
+        format!("{} ({})", engine.name, source.label)
+    }
+}
+
+/// Moves from the input line to the start of the next one and clears everything from there down,
+/// leaving the input line as it was.
+#[allow(unused_must_use)]
+fn clear_below_prompt(cursor: &mut TerminalCursor, terminal: &Terminal, t_w: u16) {
+    cursor.move_left(t_w);
+    cursor.move_down(1);
+    terminal.clear(ClearType::FromCursorDown);
 }
 
 fn input_line_from_selection(
@@ -343,16 +692,12 @@ fn input_line_from_selection(
     }
 }
 
-// mirrors opensearch schema
-#[derive(Debug)]
-pub struct Suggestions {
-    term: String,
-    sugg_terms: Vec<String>,
-    //descriptions: Vec<String>,
-    //urls: Vec<String>
-}
-
 enum UiMsg {
-    SetSuggestions(Suggestions),
+    /// What the engine's `source`th source came back with, for the input line as of `generation`.
+    SetSuggestions {
+        generation: u64,
+        source: usize,
+        result: Result<Suggestions, SuggestionError>,
+    },
     OnInput(BindableAction),
 }
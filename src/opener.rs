@@ -0,0 +1,105 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::*;
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Does whatever `open_with` says with `url`.
+pub fn open_url(url: &str, open_with: &OpenWith) -> io::Result<()> {
+    match open_with {
+        OpenWith::Browser => webbrowser::open(url).map(|_| ()),
+        OpenWith::Print => {
+            println!("{}", url);
+            Ok(())
+        }
+        OpenWith::Command(template) => {
+            // a browser that wasn't already running would otherwise keep us around until it's
+            // closed, and print its logs all over the terminal
+            let mut command = command_from_template(template, url)?;
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null());
+            // and it shouldn't be hung up on when the terminal we ran in closes
+            #[cfg(unix)]
+            std::os::unix::process::CommandExt::process_group(&mut command, 0);
+            command.spawn()?;
+            Ok(())
+        }
+        OpenWith::Terminal(template) => {
+            let status = command_from_template(template, url)?.status()?;
+            check_status(template, status)
+        }
+        OpenWith::Clipboard(template) => {
+            let mut child = command_from_template(template, "")?
+                .stdin(Stdio::piped())
+                .spawn()?;
+            child
+                .stdin
+                .take()
+                .expect("child stdin is piped")
+                .write_all(url.as_bytes())?;
+            check_status(template, child.wait()?)
+        }
+    }
+}
+
+/// Runs an engine's `command`, given as the program followed by its arguments, and waits for it.
+/// Unless it's meant for the `terminal`, it doesn't get our stdin.
+pub fn run_command(command: &[String], terminal: bool) -> io::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let status = Command::new(program)
+        .args(args)
+        .stdin(if terminal {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        })
+        .status()?;
+    check_status(&shell_words::join(command), status)
+}
+
+/// Splits a command template into a `Command`, replacing `{url}` in each argument. If there is no
+/// `{url}` in the template and `url` isn't empty, it's passed as the last argument.
+fn command_from_template(template: &str, url: &str) -> io::Result<Command> {
+    let words = shell_words::split(template)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+    let (program, args) = words
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let mut command = Command::new(program);
+    command.args(args.iter().map(|arg| arg.replace("{url}", url)));
+    if !url.is_empty() && !template.contains("{url}") {
+        command.arg(url);
+    }
+    Ok(command)
+}
+
+fn check_status(template: &str, status: std::process::ExitStatus) -> io::Result<()> {
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "'{}' exited with {}",
+            template, status
+        )))
+    }
+}
@@ -17,40 +17,1094 @@
 
 use crate::*;
 
-pub trait SuggestionAdapter {
-    fn get(url:String, term: String) -> Result<Suggestions, Box<std::error::Error>>;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+/// How many seconds a suggestion command gets before it's killed, unless the engine's
+/// `http.timeout_secs` says otherwise.
+const DEFAULT_EXEC_TIMEOUT_SECS: u64 = 3;
+
+/// How many times each suggestion command has been started, so older runs know to give up.
+static EXEC_GENERATIONS: LazyLock<Mutex<HashMap<String, u64>>> = LazyLock::new(Default::default);
+
+/// A file's entries for `FileAdapter`, along with their lowercased versions to match against.
+type FileEntries = Arc<Vec<(String, String)>>;
+
+/// A file `FileAdapter` has read, with the modification time and size it had back then.
+struct ReadFile {
+    modified: SystemTime,
+    len: u64,
+    entries: FileEntries,
+}
+
+/// Every file `FileAdapter` has read, so that each is only read again once it changes.
+static READ_FILES: LazyLock<Mutex<HashMap<PathBuf, ReadFile>>> = LazyLock::new(Default::default);
+
+/// Suggestions for a search term. Mirrors the OpenSearch suggestions schema.
+#[derive(Debug, Clone, Default)]
+pub struct Suggestions {
+    /// The term these suggestions are for, used to discard out-of-order responses.
+    pub term: String,
+    pub sugg_terms: Vec<String>,
+    /// A description for each suggestion, in the same order as `sugg_terms`. Empty strings for
+    /// suggestions without one, or empty altogether if the adapter doesn't provide any.
+    pub descriptions: Vec<String>,
+    /// URLs to open instead of searching for the suggestion, laid out like `descriptions`.
+    pub urls: Vec<String>,
+    /// Which of the engine's sources each suggestion came from, laid out like `descriptions`.
+    /// Only filled in by `merge_suggestions`.
+    pub sources: Vec<usize>,
+}
+
+impl Suggestions {
+    /// The description of the `n`th suggestion, if it has one.
+    pub fn description(&self, n: usize) -> Option<&str> {
+        self.descriptions
+            .get(n)
+            .map(String::as_str)
+            .filter(|x| !x.is_empty())
+    }
+
+    /// The URL the `n`th suggestion opens instead of a search, if it has one.
+    pub fn url(&self, n: usize) -> Option<&str> {
+        self.urls
+            .get(n)
+            .map(String::as_str)
+            .filter(|x| !x.is_empty())
+    }
+
+    /// Which of the engine's sources the `n`th suggestion came from, for merged suggestions.
+    pub fn source(&self, n: usize) -> Option<usize> {
+        self.sources.get(n).cloned()
+    }
+}
+
+/// Why fetching suggestions failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuggestionError {
+    /// The request couldn't be sent or the response couldn't be read.
+    Network(String),
+    /// The endpoint answered with a non-2xx status code.
+    HttpStatus(i32),
+    /// The response body isn't valid JSON (or XML, for the XML adapter).
+    Parse(String),
+    /// There's nothing at this path in the response.
+    MissingPath(String),
+    /// The value at `path` isn't what the adapter expects.
+    WrongType {
+        path: String,
+        expected: &'static str,
+    },
+    /// A suggestion command couldn't be run or failed.
+    Command(String),
+    /// A suggestion command took too long and was killed.
+    Timeout,
+    /// A suggestion command was killed because the same command started for a newer term.
+    Cancelled,
+    /// A suggestion file couldn't be read.
+    File(String),
+    /// The engine's HTTP settings can't be used, e.g. because of a bad proxy URL.
+    Config(String),
+}
+
+impl fmt::Display for SuggestionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SuggestionError::Network(e) => write!(f, "network error: {}", e),
+            SuggestionError::HttpStatus(code) => write!(f, "HTTP status {}", code),
+            SuggestionError::Parse(e) => write!(f, "couldn't parse response: {}", e),
+            SuggestionError::MissingPath(path) => {
+                write!(f, "nothing at '{}' in the response", path)
+            }
+            SuggestionError::WrongType { path, expected } => {
+                write!(f, "expected {} at '{}' in the response", expected, path)
+            }
+            SuggestionError::Command(e) => write!(f, "command failed: {}", e),
+            SuggestionError::Timeout => write!(f, "command timed out"),
+            SuggestionError::Cancelled => write!(f, "cancelled by a newer request"),
+            SuggestionError::File(e) => write!(f, "couldn't read file: {}", e),
+            SuggestionError::Config(e) => write!(f, "invalid HTTP settings: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SuggestionError {}
+
+/// Resolves suggestions for `search_term` with the engine's configured adapter. Blocks until the
+/// request is done, so the UI calls this from a worker thread.
+pub fn fetch_suggestions(
+    engine: &Engine,
+    search_term: &str,
+) -> Result<Suggestions, SuggestionError> {
+    let adapter = engine.suggestion_adapter.adapter();
+    let url = if adapter.uses_url() {
+        engine.format_suggestion_url(search_term)
+    } else {
+        String::new()
+    };
+    adapter.get(&url, search_term, &engine.http)
 }
+
+/// Something that can turn a search term (and the engine's suggestion URL for it) into
+/// `Suggestions`.
+///
+/// Implement this and `register_adapter` it to add your own kind of `suggestion_adapter`.
+pub trait SuggestionAdapter: Send + Sync {
+    /// `url` is the engine's `suggestion_url` with the term filled in, or empty if the adapter
+    /// doesn't use it. Adapters that fetch it should do so with `http_get` and `http`, the
+    /// engine's `[http]` settings.
+    fn get(
+        &self,
+        url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError>;
+
+    /// Whether the adapter fetches the suggestion URL. Engines whose adapter doesn't can leave
+    /// out `suggestion_url`.
+    fn uses_url(&self) -> bool {
+        true
+    }
+}
+
+/// Builds an adapter from the part of `suggestion_adapter` after `<name>:` (empty if there's no
+/// colon), or explains what's wrong with it.
+pub type AdapterConstructor =
+    dyn Fn(&str) -> Result<Box<dyn SuggestionAdapter>, String> + Send + Sync;
+
+static ADAPTER_REGISTRY: LazyLock<RwLock<HashMap<String, Arc<AdapterConstructor>>>> =
+    LazyLock::new(Default::default);
+
+/// Names that already mean something in `suggestion_adapter` or a suggestion source's `adapter`.
+const BUILTIN_ADAPTERS: &[&str] = &[
+    "opensearch",
+    "json",
+    "xml",
+    "text",
+    "exec",
+    "file",
+    "history",
+];
+
+/// Makes `suggestion_adapter = "<name>:<arg>"` (or just `"<name>"`) build an adapter with
+/// `constructor`. Has to happen before the config is loaded. Fails for the built-in names
+/// (`opensearch`, `json`, `xml`, `text`, `exec`, `file` and `history`) and ones with a `:`.
+pub fn register_adapter<F>(name: &str, constructor: F) -> Result<(), ConfigError>
+where
+    F: Fn(&str) -> Result<Box<dyn SuggestionAdapter>, String> + Send + Sync + 'static,
+{
+    let name = name.to_lowercase();
+    if BUILTIN_ADAPTERS.contains(&name.as_str()) {
+        return Err(ConfigError::new(&format!(
+            "'{}' is a built-in suggestion adapter and can't be replaced.",
+            name
+        )));
+    }
+    if name.is_empty() || name.contains(':') {
+        return Err(ConfigError::new(&format!(
+            "Invalid suggestion adapter name '{}'.",
+            name
+        )));
+    }
+    ADAPTER_REGISTRY
+        .write()
+        .unwrap()
+        .insert(name, Arc::new(constructor));
+    Ok(())
+}
+
+/// An adapter from `register_adapter`, along with the `suggestion_adapter` string it came from.
+#[derive(Clone)]
+pub struct CustomAdapter {
+    spec: String,
+    adapter: Arc<dyn SuggestionAdapter>,
+}
+
+impl CustomAdapter {
+    /// Looks `spec` up in the registry. `Ok(None)` if nothing is registered under its name.
+    pub fn from_registry(spec: &str) -> Result<Option<CustomAdapter>, String> {
+        let (name, arg) = match spec.find(':') {
+            Some(i) => (&spec[..i], &spec[i + 1..]),
+            None => (spec, ""),
+        };
+        // not called with the lock held, in case it registers adapters itself
+        let constructor = ADAPTER_REGISTRY
+            .read()
+            .unwrap()
+            .get(&name.to_lowercase())
+            .cloned();
+        let constructor = match constructor {
+            Some(constructor) => constructor,
+            None => return Ok(None),
+        };
+        Ok(Some(CustomAdapter {
+            spec: spec.to_string(),
+            adapter: Arc::from(constructor(arg)?),
+        }))
+    }
+}
+
+impl PartialEq for CustomAdapter {
+    fn eq(&self, other: &CustomAdapter) -> bool {
+        self.spec == other.spec
+    }
+}
+
+impl fmt::Display for CustomAdapter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.spec)
+    }
+}
+
+impl SuggestionAdapterName {
+    /// The adapter this name stands for.
+    pub fn adapter(&self) -> Box<dyn SuggestionAdapter> {
+        match self {
+            SuggestionAdapterName::OpenSearch => Box::new(OpenSearchAdapter),
+            SuggestionAdapterName::JsonPath { path, description } => Box::new(JsonPathAdapter {
+                path: path.clone(),
+                description: description.clone(),
+            }),
+            SuggestionAdapterName::Xml(path) => Box::new(XmlAdapter(path.clone())),
+            SuggestionAdapterName::Text(pattern) => Box::new(TextAdapter(pattern.clone())),
+            SuggestionAdapterName::Exec(command) => Box::new(ExecAdapter(command.clone())),
+            SuggestionAdapterName::File(path) => Box::new(FileAdapter(expand_tilde(path))),
+            SuggestionAdapterName::Custom(custom) => Box::new(custom.clone()),
+        }
+    }
+}
+
+impl SuggestionAdapter for CustomAdapter {
+    fn get(
+        &self,
+        url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        self.adapter.get(url, term, http)
+    }
+
+    fn uses_url(&self) -> bool {
+        self.adapter.uses_url()
+    }
+}
+
+fn parse_json(body: &str) -> Result<json::JsonValue, SuggestionError> {
+    json::parse(body).map_err(|e| SuggestionError::Parse(e.to_string()))
+}
+
+fn expect_array<'a>(
+    value: &'a json::JsonValue,
+    path: &str,
+) -> Result<&'a json::JsonValue, SuggestionError> {
+    if value.is_null() {
+        return Err(SuggestionError::MissingPath(path.to_string()));
+    }
+    if !value.is_array() {
+        return Err(SuggestionError::WrongType {
+            path: path.to_string(),
+            expected: "an array",
+        });
+    }
+    Ok(value)
+}
+
 pub struct OpenSearchAdapter;
+impl OpenSearchAdapter {
+    /// Parses an OpenSearch suggestions response:
+    /// `[term, [suggestions...], [descriptions...], [urls...]]`, where the last two are optional.
+    /// Non-string suggestions are skipped.
+    pub fn parse(body: &str) -> Result<Suggestions, SuggestionError> {
+        let data = parse_json(body)?;
+        let term = match data[0] {
+            json::JsonValue::Null => return Err(SuggestionError::MissingPath("[0]".into())),
+            ref x => x.as_str().ok_or(SuggestionError::WrongType {
+                path: "[0]".into(),
+                expected: "a string",
+            })?,
+        };
+        let mut suggs = Suggestions {
+            term: term.to_string(),
+            ..Default::default()
+        };
+        for (i, sugg) in expect_array(&data[1], "[1]")?.members().enumerate() {
+            if let Some(sugg) = sugg.as_str() {
+                suggs.sugg_terms.push(sugg.to_string());
+                // indexing anything that isn't an array gives null, so missing arrays are fine
+                suggs
+                    .descriptions
+                    .push(data[2][i].as_str().unwrap_or_default().to_string());
+                suggs
+                    .urls
+                    .push(data[3][i].as_str().unwrap_or_default().to_string());
+            }
+        }
+        Ok(suggs)
+    }
+}
 impl SuggestionAdapter for OpenSearchAdapter {
-    fn get(url: String, _term: String) -> Result<Suggestions, Box<std::error::Error>> {
-        let text = minreq::get(url).send()?.body;
-        let data = json::parse(&text)?;
-        let term = data[0]
-            .as_str()
-            .ok_or("first array value not a string")?
-            .to_string();
-        let sugg_terms: Vec<String> = data[1].members().map(|opt|opt.as_str().expect("one of the values in the second value (which should be an array) is not a string").to_string()).collect(); // todo: error handling
-        Ok(Suggestions { term, sugg_terms })
-    }
-}
-pub struct JsonPathAdapter(pub String);
+    fn get(
+        &self,
+        url: &str,
+        _term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        OpenSearchAdapter::parse(&http_get(url, http)?)
+    }
+}
+
+/// Picks the suggestions (and optionally their descriptions) out of arbitrary JSON with a
+/// `JsonPath`.
+pub struct JsonPathAdapter {
+    pub path: JsonPath,
+    pub description: Option<JsonPath>,
+}
+impl SuggestionAdapter for JsonPathAdapter {
+    fn get(
+        &self,
+        url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        self.parse(&http_get(url, http)?, term.to_string())
+    }
+}
 impl JsonPathAdapter {
-    pub fn get(self, url: String, term: String) -> Result<Suggestions, Box<std::error::Error>> {
-        let text = minreq::get(url).send()?.body;
-        let data = json::parse(&text)?;
-        let path = self.0.split('.');
-        let mut obj = data;
+    /// Parses a JSON response. A path without wildcards has to lead to an array of suggestions,
+    /// while a path with them (like `items[*].title`) gives the suggestions directly. Non-string
+    /// suggestions are skipped.
+    pub fn parse(&self, body: &str, term: String) -> Result<Suggestions, SuggestionError> {
+        let data = parse_json(body)?;
+        let sugg_values = select_list(&self.path, &data)?;
+        let description_values = match self.description {
+            Some(ref path) => select_list(path, &data)?,
+            None => Vec::new(),
+        };
+        let mut suggs = Suggestions {
+            term,
+            ..Default::default()
+        };
+        for (i, sugg) in sugg_values.iter().enumerate() {
+            if let Some(sugg) = sugg.as_str() {
+                suggs.sugg_terms.push(sugg.to_string());
+                if self.description.is_some() {
+                    let description = description_values.get(i).and_then(|x| x.as_str());
+                    suggs
+                        .descriptions
+                        .push(description.unwrap_or_default().to_string());
+                }
+            }
+        }
+        Ok(suggs)
+    }
+}
+
+fn select_list<'a>(
+    path: &JsonPath,
+    data: &'a json::JsonValue,
+) -> Result<Vec<&'a json::JsonValue>, SuggestionError> {
+    let selected = path.select(data).map_err(SuggestionError::MissingPath)?;
+    if path.has_wildcard() {
+        return Ok(selected);
+    }
+    // without a wildcard, `select` found exactly one value
+    Ok(expect_array(selected[0], &path.to_string())?
+        .members()
+        .collect())
+}
+
+/// Picks suggestions out of XML, from the text or an attribute of the elements at an `XmlPath`.
+/// Covers the OpenSearch XML format as well as things like Google's `output=toolbar`.
+pub struct XmlAdapter(pub XmlPath);
+impl SuggestionAdapter for XmlAdapter {
+    fn get(
+        &self,
+        url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        self.parse(&http_get(url, http)?, term.to_string())
+    }
+}
+impl XmlAdapter {
+    pub fn parse(&self, body: &str, term: String) -> Result<Suggestions, SuggestionError> {
+        let doc =
+            roxmltree::Document::parse(body).map_err(|e| SuggestionError::Parse(e.to_string()))?;
+        let sugg_terms = self
+            .0
+            .select(&doc)
+            .map_err(SuggestionError::MissingPath)?
+            .iter()
+            .filter_map(|node| self.0.value(node))
+            .map(|x: String| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
+        Ok(Suggestions {
+            term,
+            sugg_terms,
+            ..Default::default()
+        })
+    }
+}
+
+/// A regex that picks the suggestion out of a line: the first capture group if it has one,
+/// otherwise the whole match.
+#[derive(Debug, Clone)]
+pub struct LinePattern(regex::Regex);
+
+impl LinePattern {
+    fn extract<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let captures = self.0.captures(line)?;
+        captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map(|x| x.as_str())
+    }
+}
+
+impl PartialEq for LinePattern {
+    fn eq(&self, other: &LinePattern) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl fmt::Display for LinePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl FromStr for LinePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        regex::Regex::new(s)
+            .map(LinePattern)
+            .map_err(|e| format!("Invalid regex '{}': {}", s, e))
+    }
+}
+
+/// One suggestion per line of plain text, optionally filtered through a `LinePattern`. Blank
+/// lines and lines the pattern doesn't match are skipped.
+pub struct TextAdapter(pub Option<LinePattern>);
+impl SuggestionAdapter for TextAdapter {
+    fn get(
+        &self,
+        url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        Ok(self.parse(&http_get(url, http)?, term.to_string()))
+    }
+}
+impl TextAdapter {
+    pub fn parse(&self, body: &str, term: String) -> Suggestions {
+        let sugg_terms = body
+            .lines()
+            .filter_map(|line| match self.0 {
+                Some(ref pattern) => pattern.extract(line),
+                None => Some(line),
+            })
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(str::to_string)
+            .collect();
+        Suggestions {
+            term,
+            sugg_terms,
+            ..Default::default()
+        }
+    }
+}
+
+/// Runs a local command and uses its output as plain text, one suggestion per line. Any argument
+/// that's exactly `%s` is replaced by the search term; without one, the term is written to stdin.
+/// `%s` inside a longer argument is left alone, so it can't be used inside `sh -c '...'` code,
+/// where the term would be run as shell code. Pass it as an argument to the script instead.
+///
+/// Starting the same command again kills the previous run, since its term is out of date by then,
+/// and `http.timeout_secs` (3 by default) limits how long it can take.
+pub struct ExecAdapter(pub String);
+impl SuggestionAdapter for ExecAdapter {
+    fn get(
+        &self,
+        _url: &str,
+        term: &str,
+        http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        let words =
+            shell_words::split(&self.0).map_err(|e| SuggestionError::Command(e.to_string()))?;
+        let term_as_arg = words.iter().any(|x| x == "%s");
+        let words: Vec<&str> = words
+            .iter()
+            .map(|x| if x == "%s" { term } else { x.as_str() })
+            .collect();
+        let (program, args) = words
+            .split_first()
+            .ok_or_else(|| SuggestionError::Command("empty command".into()))?;
+
+        let generation = {
+            let mut generations = EXEC_GENERATIONS.lock().unwrap();
+            let generation = generations.entry(self.0.clone()).or_insert(0);
+            *generation += 1;
+            *generation
+        };
+        let is_stale = || EXEC_GENERATIONS.lock().unwrap().get(&self.0) != Some(&generation);
 
-        for segment in path {
-            obj = obj[segment].take();
+        let mut command = Command::new(program);
+        command
+            .args(args)
+            .stdin(if term_as_arg {
+                Stdio::null()
+            } else {
+                Stdio::piped()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        // in its own process group, so that whatever it starts can be killed along with it
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let mut child = command
+            .spawn()
+            .map_err(|e| SuggestionError::Command(format!("{}: {}", program, e)))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // the command may well exit without reading it
+            let _ = writeln!(stdin, "{}", term);
         }
+        // read on another thread so a full pipe can't block the command
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
 
-        if !obj.is_array() {
-            return Err(Box::new(ConfigError::new(&format!("JsonPath '{}' doesn't lead to an array!", &self.0))));
+        let timeout = Duration::from_secs(http.timeout_secs.unwrap_or(DEFAULT_EXEC_TIMEOUT_SECS));
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            if has_exited(&mut child).map_err(|e| SuggestionError::Command(e.to_string()))? {
+                // anything it left running in the background could hold on to stdout
+                kill_process_group(&child);
+                break child
+                    .wait()
+                    .map_err(|e| SuggestionError::Command(e.to_string()))?;
+            }
+            let error = if Instant::now() >= deadline {
+                SuggestionError::Timeout
+            } else if is_stale() {
+                SuggestionError::Cancelled
+            } else {
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            };
+            kill_process_group(&child);
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(error);
+        };
+        if !status.success() {
+            return Err(SuggestionError::Command(format!(
+                "{} exited with {}",
+                program, status
+            )));
         }
+        let output = reader
+            .join()
+            .unwrap()
+            .map_err(|e| SuggestionError::Command(e.to_string()))?;
+        Ok(TextAdapter(None).parse(&String::from_utf8_lossy(&output), term.to_string()))
+    }
+
+    fn uses_url(&self) -> bool {
+        false
+    }
+}
+
+/// Whether `child` has exited, without reaping it. Until it's reaped, its process group can't be
+/// reused by anything else, so `kill_process_group` can't hit an unrelated one.
+#[cfg(unix)]
+fn has_exited(child: &mut std::process::Child) -> io::Result<bool> {
+    // SAFETY: siginfo_t is plain old data, and all zeroes is a valid value
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    // SAFETY: info is valid for writes, and WNOWAIT leaves the child unreaped
+    let result = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            child.id() as libc::id_t,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // with WNOHANG, nothing is filled in while the child is still running
+    Ok(info.si_signo != 0)
+}
+#[cfg(not(unix))]
+fn has_exited(child: &mut std::process::Child) -> io::Result<bool> {
+    child.try_wait().map(|status| status.is_some())
+}
+
+/// Kills everything in the process group `child` was started in, such as the rest of an `sh -c`
+/// pipeline, so that none of it keeps the output pipe open. `child` must not have been reaped yet.
+fn kill_process_group(child: &std::process::Child) {
+    #[cfg(unix)]
+    // SAFETY: kill() has no memory safety requirements, and the group is the child's own
+    unsafe {
+        libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = child;
+}
+
+/// Suggestions from a local file with one entry per line, matched against the term without any
+/// network calls. Entries starting with the term come first, in file order, followed by ones
+/// containing it and then fuzzy matches (the term's chars in order), tightest first.
+pub struct FileAdapter(pub PathBuf);
+impl SuggestionAdapter for FileAdapter {
+    fn get(
+        &self,
+        _url: &str,
+        term: &str,
+        _http: &HttpSettings,
+    ) -> Result<Suggestions, SuggestionError> {
+        Ok(FileAdapter::filter(&self.entries()?, term.to_string()))
+    }
+
+    fn uses_url(&self) -> bool {
+        false
+    }
+}
+impl FileAdapter {
+    /// The file's entries, only read from disk if it changed since the last time.
+    fn entries(&self) -> Result<FileEntries, SuggestionError> {
+        let error = |e: io::Error| SuggestionError::File(format!("{}: {}", self.0.display(), e));
+        let metadata = fs::metadata(&self.0).map_err(error)?;
+        let (modified, len) = (metadata.modified().map_err(error)?, metadata.len());
+        if let Some(file) = READ_FILES.lock().unwrap().get(&self.0) {
+            if file.modified == modified && file.len == len {
+                return Ok(file.entries.clone());
+            }
+        }
+        let text = fs::read_to_string(&self.0).map_err(error)?;
+        let entries = Arc::new(split_entries(&text));
+        let file = ReadFile {
+            modified,
+            len,
+            entries: entries.clone(),
+        };
+        READ_FILES.lock().unwrap().insert(self.0.clone(), file);
+        Ok(entries)
+    }
+
+    fn filter(entries: &[(String, String)], term: String) -> Suggestions {
+        let lower_term = term.to_lowercase();
+        let mut ranked: Vec<((u8, usize), &str)> = entries
+            .iter()
+            .filter_map(|(x, lower)| Some((match_rank(lower, &lower_term)?, x.as_str())))
+            .collect();
+        // stable, so equally good matches stay in file order
+        ranked.sort_by_key(|(rank, _)| *rank);
+        Suggestions {
+            term,
+            sugg_terms: ranked.into_iter().map(|(_, x)| x.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+}
+
+fn split_entries(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|x| (x.to_string(), x.to_lowercase()))
+        .collect()
+}
+
+/// How well `candidate` matches `term`, lower is better: prefix matches, then substring matches
+/// (earliest first), then subsequence matches (shortest span first).
+fn match_rank(candidate: &str, term: &str) -> Option<(u8, usize)> {
+    if candidate.starts_with(term) {
+        return Some((0, 0));
+    }
+    if let Some(i) = candidate.find(term) {
+        return Some((1, i));
+    }
+    let mut term_chars = term.chars().peekable();
+    let mut span = None;
+    for (i, c) in candidate.char_indices() {
+        match term_chars.peek() {
+            Some(&t) if t == c => {
+                term_chars.next();
+                span = Some(span.map_or((i, i), |(start, _)| (start, i)));
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+    match (term_chars.peek(), span) {
+        (None, Some((start, end))) => Some((2, end - start)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_search() {
+        let suggs =
+            OpenSearchAdapter::parse(r#"["rust",["rust lang",5,null,"rust book"],[],[]]"#).unwrap();
+        assert_eq!(suggs.term, "rust");
+        assert_eq!(suggs.sugg_terms, vec!["rust lang", "rust book"]);
+        assert_eq!(suggs.description(0), None);
+        assert_eq!(suggs.url(1), None);
+
+        assert_eq!(
+            OpenSearchAdapter::parse(r#"{"rust": []}"#).unwrap_err(),
+            SuggestionError::MissingPath("[0]".into())
+        );
+        assert_eq!(
+            OpenSearchAdapter::parse(r#"[1, []]"#).unwrap_err(),
+            SuggestionError::WrongType {
+                path: "[0]".into(),
+                expected: "a string"
+            }
+        );
+        assert_eq!(
+            OpenSearchAdapter::parse(r#"["rust", "rust lang"]"#).unwrap_err(),
+            SuggestionError::WrongType {
+                path: "[1]".into(),
+                expected: "an array"
+            }
+        );
+        match OpenSearchAdapter::parse("<html>") {
+            Err(SuggestionError::Parse(_)) => {}
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn open_search_descriptions_and_urls() {
+        let suggs = OpenSearchAdapter::parse(
+            r#"["rust", ["Rust", 1, "Rust Belt"],
+                ["Rust is an iron oxide", "", ""],
+                ["https://en.wikipedia.org/wiki/Rust", "", "https://en.wikipedia.org/wiki/Rust_Belt"]]"#,
+        )
+        .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["Rust", "Rust Belt"]);
+        assert_eq!(suggs.description(0), Some("Rust is an iron oxide"));
+        assert_eq!(suggs.description(1), None);
+        assert_eq!(suggs.url(0), Some("https://en.wikipedia.org/wiki/Rust"));
+        assert_eq!(
+            suggs.url(1),
+            Some("https://en.wikipedia.org/wiki/Rust_Belt")
+        );
+    }
+
+    fn json_path_adapter(path: &str, description: Option<&str>) -> JsonPathAdapter {
+        JsonPathAdapter {
+            path: path.parse().unwrap(),
+            description: description.map(|x| x.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn json_path() {
+        let body = r#"{"data": {"names": ["pics", {"name": "aww"}, "rust"], "count": 3}}"#;
+        let suggs = json_path_adapter("data.names", None)
+            .parse(body, "r".into())
+            .unwrap();
+        assert_eq!(suggs.term, "r");
+        assert_eq!(suggs.sugg_terms, vec!["pics", "rust"]);
+
+        assert_eq!(
+            json_path_adapter("data.items.names", None)
+                .parse(body, "r".into())
+                .unwrap_err(),
+            SuggestionError::MissingPath("data.items".into())
+        );
+        assert_eq!(
+            json_path_adapter("data.count", None)
+                .parse(body, "r".into())
+                .unwrap_err(),
+            SuggestionError::WrongType {
+                path: "data.count".into(),
+                expected: "an array"
+            }
+        );
+    }
+
+    #[test]
+    fn json_path_objects_with_descriptions() {
+        let body = r#"{"data": {"children": [
+            {"data": {"display_name": "rust", "title": "The Rust Programming Language"}},
+            {"data": {"display_name": 7, "title": "Not a name"}},
+            {"data": {"display_name": "rust_gamedev"}}
+        ]}}"#;
+        let suggs = json_path_adapter(
+            "data.children[*].data.display_name",
+            Some("$.data.children[*].data.title"),
+        )
+        .parse(body, "rust".into())
+        .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["rust", "rust_gamedev"]);
+        assert_eq!(suggs.description(0), Some("The Rust Programming Language"));
+        assert_eq!(suggs.description(1), None);
+    }
+
+    fn xml_adapter(path: &str) -> XmlAdapter {
+        XmlAdapter(path.parse().unwrap())
+    }
+
+    #[test]
+    fn xml_attributes() {
+        let body = r#"<?xml version="1.0"?><toplevel>
+            <CompleteSuggestion><suggestion data="rust lang"/></CompleteSuggestion>
+            <CompleteSuggestion><suggestion data="rust book"/></CompleteSuggestion>
+            <CompleteSuggestion><other data="nope"/></CompleteSuggestion>
+        </toplevel>"#;
+        let suggs = xml_adapter("toplevel/CompleteSuggestion/suggestion@data")
+            .parse(body, "rust".into())
+            .unwrap();
+        assert_eq!(suggs.term, "rust");
+        assert_eq!(suggs.sugg_terms, vec!["rust lang", "rust book"]);
+
+        assert_eq!(
+            xml_adapter("toplevel/Nope/suggestion@data")
+                .parse(body, "rust".into())
+                .unwrap_err(),
+            SuggestionError::MissingPath("toplevel/Nope".into())
+        );
+        match xml_adapter("a").parse("<a>", "rust".into()) {
+            Err(SuggestionError::Parse(_)) => {}
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn xml_text_with_namespaces() {
+        let body = r#"<SearchSuggestion xmlns="http://opensearch.org/searchsuggest2" version="2.0">
+            <Query>rust</Query>
+            <Section>
+                <Item><Text xml:space="preserve">Rust</Text></Item>
+                <Item><Text xml:space="preserve">Rust <b>Belt</b></Text></Item>
+            </Section>
+        </SearchSuggestion>"#;
+        let suggs = xml_adapter("/SearchSuggestion/Section/*/Text")
+            .parse(body, "rust".into())
+            .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["Rust", "Rust Belt"]);
+    }
+
+    #[test]
+    fn text_lines() {
+        let body = "rust lang\r\n\n  rust book \n";
+        let suggs = TextAdapter(None).parse(body, "rust".into());
+        assert_eq!(suggs.term, "rust");
+        assert_eq!(suggs.sugg_terms, vec!["rust lang", "rust book"]);
+
+        let body = "1\trust lang\t42\n# comment\n2\trust book\t7\n";
+        let pattern = r"^\d+\t([^\t]+)".parse().unwrap();
+        let suggs = TextAdapter(Some(pattern)).parse(body, "rust".into());
+        assert_eq!(suggs.sugg_terms, vec!["rust lang", "rust book"]);
+
+        let suggs = TextAdapter(Some("rust \\w+".parse().unwrap())).parse(body, "rust".into());
+        assert_eq!(suggs.sugg_terms, vec!["rust lang", "rust book"]);
+        assert!("(".parse::<LinePattern>().is_err());
+    }
+
+    #[test]
+    fn exec() {
+        let http = &HttpSettings::default();
+        // the term is one argument, and the `%s`s in the format string aren't touched
+        let suggs = ExecAdapter("printf '%s|%s\\n' %s b".into())
+            .get("", "rust lang", http)
+            .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["rust lang|b"]);
+        // only a whole argument is a placeholder, so this one gets the term on stdin
+        let suggs = ExecAdapter("sh -c 'read x; echo \"%s=$x\"'".into())
+            .get("", "rust", http)
+            .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["%s=rust"]);
+
+        let suggs = ExecAdapter("sed 's/$/ lang/'".into())
+            .get("", "rust", http)
+            .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["rust lang"]);
+
+        assert_eq!(
+            ExecAdapter("false".into()).get("", "x", http).unwrap_err(),
+            SuggestionError::Command("false exited with exit status: 1".into())
+        );
+        match ExecAdapter("sefr-no-such-command".into()).get("", "x", http) {
+            Err(SuggestionError::Command(_)) => {}
+            other => panic!("expected a command error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn exec_timeout() {
+        let http = &HttpSettings {
+            timeout_secs: Some(1),
+            ..Default::default()
+        };
+        let started = Instant::now();
+        assert_eq!(
+            ExecAdapter("sleep 5".into())
+                .get("", "x", http)
+                .unwrap_err(),
+            SuggestionError::Timeout
+        );
+        assert!(started.elapsed() < Duration::from_secs(DEFAULT_EXEC_TIMEOUT_SECS));
+    }
+
+    #[test]
+    fn exec_newer_run_cancels_older() {
+        let http = &HttpSettings::default();
+        let slow = thread::spawn(|| {
+            ExecAdapter("sleep 2 %s".into()).get("", "1", &HttpSettings::default())
+        });
+        thread::sleep(Duration::from_millis(300));
+        let started = Instant::now();
+        assert!(ExecAdapter("sleep 2 %s".into()).get("", "0", http).is_ok());
+        assert_eq!(
+            slow.join().unwrap().unwrap_err(),
+            SuggestionError::Cancelled
+        );
+        assert!(started.elapsed() < Duration::from_secs(DEFAULT_EXEC_TIMEOUT_SECS));
+    }
+
+    #[test]
+    fn exec_leftover_processes_are_killed() {
+        let started = Instant::now();
+        let suggs = ExecAdapter("sh -c 'sleep 10 & echo $0' %s".into())
+            .get("", "x", &HttpSettings::default())
+            .unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["x"]);
+        assert!(started.elapsed() < Duration::from_secs(DEFAULT_EXEC_TIMEOUT_SECS));
+    }
+
+    #[test]
+    fn file_matching() {
+        let text = "rustjerk\n\nlearnrust\nRust\nrust_gamedev\nr_u_s_t\nrubyonrails\n";
+        let entries = split_entries(text);
+        let suggs = FileAdapter::filter(&entries, "rust".into());
+        assert_eq!(
+            suggs.sugg_terms,
+            vec!["rustjerk", "Rust", "rust_gamedev", "learnrust", "r_u_s_t"]
+        );
+        assert_eq!(
+            FileAdapter::filter(&entries, "rgd".into()).sugg_terms,
+            vec!["rust_gamedev"]
+        );
+
+        let http = &HttpSettings::default();
+        match FileAdapter("/nonexistent/sefr.txt".into()).get("", "x", http) {
+            Err(SuggestionError::File(_)) => {}
+            other => panic!("expected a file error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn file_is_read_again_once_changed() {
+        let path = std::env::temp_dir().join(format!("sefr-file-{}.txt", std::process::id()));
+        let http = &HttpSettings::default();
+        let adapter = FileAdapter(path.clone());
+        fs::write(&path, "rust\n").unwrap();
+        assert_eq!(
+            adapter.get("", "ru", http).unwrap().sugg_terms,
+            vec!["rust"]
+        );
+        assert!(Arc::ptr_eq(
+            &adapter.entries().unwrap(),
+            &adapter.entries().unwrap()
+        ));
+
+        fs::write(&path, "rust\nruby\n").unwrap();
+        let suggs = adapter.get("", "ru", http).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(suggs.sugg_terms, vec!["rust", "ruby"]);
+    }
+
+    struct StaticAdapter(Vec<String>);
+    impl SuggestionAdapter for StaticAdapter {
+        fn get(
+            &self,
+            _url: &str,
+            term: &str,
+            _http: &HttpSettings,
+        ) -> Result<Suggestions, SuggestionError> {
+            Ok(Suggestions {
+                term: term.to_string(),
+                sugg_terms: self.0.clone(),
+                ..Default::default()
+            })
+        }
+
+        fn uses_url(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn registry() {
+        register_adapter("static", |arg| {
+            if arg.is_empty() {
+                return Err("static needs a list".into());
+            }
+            Ok(Box::new(StaticAdapter(
+                arg.split(',').map(str::to_string).collect(),
+            )))
+        })
+        .unwrap();
+        let config = |adapter: &str| {
+            parse_config(&format!(
+                "[engines._default]\nname = \"x\"\nsearch_url = \"https://example.com/?q=%s\"\n\
+                 suggestion_adapter = \"{}\"\n[keybinds]\n",
+                adapter
+            ))
+        };
+        let config = config("Static:rust,rust lang").unwrap();
+        let engine = &config.engines[""];
+        assert!(engine.has_suggestions());
+        assert_eq!(
+            engine.suggestion_adapter.to_string(),
+            "Static:rust,rust lang"
+        );
+        let suggs = fetch_suggestions(engine, "ru").unwrap();
+        assert_eq!(suggs.term, "ru");
+        assert_eq!(suggs.sugg_terms, vec!["rust", "rust lang"]);
+
+        assert!("static".parse::<SuggestionAdapterName>().is_err());
+        assert!("unregistered:x".parse::<SuggestionAdapterName>().is_err());
+
+        let static_adapter = |_: &str| -> Result<Box<dyn SuggestionAdapter>, String> {
+            Ok(Box::new(StaticAdapter(vec![])))
+        };
+        assert!(register_adapter("JSON", static_adapter).is_err());
+        assert!(register_adapter("history", static_adapter).is_err());
+        assert!(register_adapter("a:b", static_adapter).is_err());
+        assert!(matches!(
+            "json:names".parse().unwrap(),
+            SuggestionAdapterName::JsonPath { .. }
+        ));
 
-        let sugg_terms: Vec<String> = obj.members().map(|opt|opt.as_str().expect("non-string value under jsonpath target").to_string()).collect(); // todo: error handling
-        Ok(Suggestions { term, sugg_terms })
+        // a constructor can register more adapters without deadlocking
+        register_adapter("lazy", move |_| {
+            register_adapter("inner", static_adapter).unwrap();
+            static_adapter("")
+        })
+        .unwrap();
+        assert!("lazy".parse::<SuggestionAdapterName>().is_ok());
+        assert!("inner".parse::<SuggestionAdapterName>().is_ok());
     }
 }
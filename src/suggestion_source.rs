@@ -0,0 +1,289 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use crate::*;
+
+use crate::config::{deserialize_opt_color, serialize_opt_color};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Where a source gets its suggestions from: past queries, or any suggestion adapter.
+#[derive(Clone, PartialEq)]
+pub enum SourceKind {
+    History,
+    Adapter(SuggestionAdapterName),
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceKind::History => f.write_str("history"),
+            SourceKind::Adapter(adapter) => write!(f, "{}", adapter),
+        }
+    }
+}
+
+impl FromStr for SourceKind {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("history") {
+            return Ok(SourceKind::History);
+        }
+        s.parse().map(SourceKind::Adapter)
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        FromStr::from_str(&s).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for SourceKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.to_string().serialize(serializer)
+    }
+}
+
+/// One of the places an engine gets suggestions from, as defined by an
+/// `[[engines.PREFIX.suggestion_sources]]` table in the config.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SuggestionSource {
+    pub adapter: SourceKind,
+    /// Like the engine's `suggestion_url`, for adapters that fetch one.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+    /// Shown next to suggestions from this source.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// What color suggestions from this source are drawn in.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_opt_color",
+        serialize_with = "serialize_opt_color",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub color: Option<Color>,
+}
+
+impl SuggestionSource {
+    pub fn is_history(&self) -> bool {
+        self.adapter == SourceKind::History
+    }
+
+    /// Whether the source's adapter fetches a URL but it wasn't given one.
+    pub fn is_missing_url(&self) -> bool {
+        match self.adapter {
+            SourceKind::Adapter(ref adapter) => adapter.uses_url() && self.url.is_empty(),
+            SourceKind::History => false,
+        }
+    }
+
+    fn new(adapter: SourceKind, url: String) -> SuggestionSource {
+        SuggestionSource {
+            adapter,
+            url,
+            label: String::new(),
+            color: None,
+        }
+    }
+}
+
+impl Engine {
+    /// The engine's `suggestion_sources`, or if it has none, past queries (unless `history` is
+    /// off) followed by its `suggestion_url`/`suggestion_adapter`.
+    pub fn sources(&self, history: bool) -> Vec<SuggestionSource> {
+        if !self.suggestion_sources.is_empty() {
+            return self.suggestion_sources.clone();
+        }
+        let mut sources = Vec::new();
+        if history {
+            sources.push(SuggestionSource::new(SourceKind::History, String::new()));
+        }
+        if self.has_suggestions() {
+            sources.push(SuggestionSource::new(
+                SourceKind::Adapter(self.suggestion_adapter.clone()),
+                self.suggestion_url.clone(),
+            ));
+        }
+        sources
+    }
+}
+
+/// Gets suggestions for `search_term` from one of `engine`'s sources. History sources need the
+/// loaded `history` and the engine's `prefix`, and give nothing without them. Other sources
+/// block until they're done, so the UI calls this from a worker thread for them.
+pub fn fetch_from_source(
+    engine: &Engine,
+    prefix: &str,
+    source: &SuggestionSource,
+    history: Option<&History>,
+    search_term: &str,
+) -> Result<Suggestions, SuggestionError> {
+    match source.adapter {
+        SourceKind::History => Ok(Suggestions {
+            term: search_term.to_string(),
+            sugg_terms: history
+                .map(|x| x.matches(prefix, search_term))
+                .unwrap_or_default(),
+            ..Default::default()
+        }),
+        SourceKind::Adapter(ref name) => {
+            let adapter = name.adapter();
+            let url = if adapter.uses_url() {
+                engine.format_url(&source.url, search_term)
+            } else {
+                String::new()
+            };
+            adapter.get(&url, search_term, &engine.http)
+        }
+    }
+}
+
+/// Merges the results from an engine's sources (`None` for ones that haven't answered) into one
+/// list, in source order, without duplicates. Where a suggestion comes from several sources, the
+/// first one wins, but later ones can fill in a missing description or URL.
+pub fn merge_suggestions(results: &[Option<Suggestions>]) -> Option<Suggestions> {
+    let mut merged: Option<Suggestions> = None;
+    for (source, suggs) in results.iter().enumerate() {
+        let suggs = match suggs {
+            Some(suggs) => suggs,
+            None => continue,
+        };
+        let merged = merged.get_or_insert_with(|| Suggestions {
+            term: suggs.term.clone(),
+            ..Default::default()
+        });
+        for (n, sugg) in suggs.sugg_terms.iter().enumerate() {
+            let description = suggs.description(n).unwrap_or_default();
+            let url = suggs.url(n).unwrap_or_default();
+            match merged.sugg_terms.iter().position(|x| x == sugg) {
+                Some(i) => {
+                    if merged.descriptions[i].is_empty() {
+                        merged.descriptions[i] = description.to_string();
+                    }
+                    if merged.urls[i].is_empty() {
+                        merged.urls[i] = url.to_string();
+                    }
+                }
+                None => {
+                    merged.sugg_terms.push(sugg.clone());
+                    merged.descriptions.push(description.to_string());
+                    merged.urls.push(url.to_string());
+                    merged.sources.push(source);
+                }
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggs(terms: &[&str], descriptions: &[&str]) -> Option<Suggestions> {
+        Some(Suggestions {
+            term: "ru".into(),
+            sugg_terms: terms.iter().map(|x| x.to_string()).collect(),
+            descriptions: descriptions.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn merge() {
+        let merged = merge_suggestions(&[
+            suggs(&["rust", "rugby"], &[]),
+            None,
+            suggs(&["rust", "ruby"], &["A language", "Also a language"]),
+        ])
+        .unwrap();
+        assert_eq!(merged.term, "ru");
+        assert_eq!(merged.sugg_terms, vec!["rust", "rugby", "ruby"]);
+        assert_eq!(merged.description(0), Some("A language"));
+        assert_eq!(merged.description(1), None);
+        assert_eq!(merged.description(2), Some("Also a language"));
+        assert_eq!(merged.sources, vec![0, 0, 2]);
+        assert!(merge_suggestions(&[None, None]).is_none());
+    }
+
+    #[test]
+    fn sources_from_config() {
+        let config = parse_config(
+            r#"
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+            suggestion_url = "https://www.google.com/complete/search?client=firefox&q=%s"
+
+            [[engines._default.suggestion_sources]]
+            adapter = "file:~/docs.txt"
+            label = "docs"
+            color = "Cyan"
+
+            [[engines._default.suggestion_sources]]
+            adapter = "history"
+
+            [engines.w]
+            name = "Wikipedia"
+            search_url = "https://en.wikipedia.org/wiki/%s"
+            suggestion_url = "https://en.wikipedia.org/w/api.php?action=opensearch&search=%s"
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        let sources = config.engines[""].sources(true);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].adapter.to_string(), "file:~/docs.txt");
+        assert_eq!(sources[0].label, "docs");
+        assert_eq!(sources[0].color, Some(Color::Cyan));
+        assert!(sources[1].is_history());
+
+        // engines without any get history and their suggestion url
+        let sources = config.engines["w"].sources(true);
+        assert!(sources[0].is_history());
+        assert_eq!(sources[1].url, config.engines["w"].suggestion_url);
+        assert_eq!(config.engines["w"].sources(false).len(), 1);
+    }
+
+    #[test]
+    fn url_is_required() {
+        let config = |source: &str| {
+            parse_config(&format!(
+                "[engines._default]\nname = \"x\"\nsearch_url = \"https://x/?q=%s\"\n\
+                 [[engines._default.suggestion_sources]]\n{}\n[keybinds]\n",
+                source
+            ))
+        };
+        assert!(config("adapter = \"json:names\"").is_err());
+        assert!(config("adapter = \"opensearch\"\nurl = \"\"").is_err());
+        assert!(config("adapter = \"opensearch\"\nurl = \"https://x/?q=%s\"").is_ok());
+        assert!(config("adapter = \"exec:echo hi\"").is_ok());
+        assert!(config("adapter = \"history\"").is_ok());
+    }
+}
@@ -0,0 +1,423 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use percent_encoding::{
+    define_encode_set, utf8_percent_encode, DEFAULT_ENCODE_SET, PATH_SEGMENT_ENCODE_SET,
+};
+
+define_encode_set! {
+    /// What's encoded in search terms by default: on top of the usual, anything that would end
+    /// or change a query string parameter.
+    pub QUERY_COMPONENT_ENCODE_SET = [DEFAULT_ENCODE_SET] | {'%', '+', '&', '=', ';'}
+}
+
+/// What a placeholder in a template stands for.
+#[derive(Debug, Clone, PartialEq)]
+enum Placeholder {
+    /// `%s`, or `{query}` if the engine has no parameter called `query`: the whole search term.
+    Term,
+    /// `%1`, `{1}` or `{name}`: one of the parts the search term is split into (0-based).
+    Param(usize),
+}
+
+/// Something done to a placeholder's value, like `lower` in `{query|lower}`.
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Lower,
+    Upper,
+    /// `replace:FROM,TO`
+    Replace(String, String),
+    Base64,
+}
+
+/// How a placeholder's value is encoded once its filters are applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Encoding {
+    /// The engine's own, with `space_becomes`.
+    Engine,
+    /// `raw`: not at all.
+    Raw,
+    /// `path`: as a path segment, so slashes are encoded too.
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder(Placeholder, Vec<Filter>, Encoding),
+}
+
+/// A URL template, like an engine's `search_url` or `suggestion_url`, with placeholders for the
+/// search term or parts of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses `template`, where `params` are the engine's parameter names for `{name}`.
+    ///
+    /// `%N` only counts as a placeholder when it isn't followed by a hex digit, so that percent
+    /// escapes like `%20` are left alone. `{N}` always does. Placeholders in braces can have
+    /// filters, like `{query|lower|path}`.
+    pub fn parse(template: &str, params: &[String]) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(c) = rest.chars().next() {
+            let (placeholder, len) = match c {
+                '%' => match percent_placeholder(rest) {
+                    Some(placeholder) => (
+                        Some(Part::Placeholder(placeholder, Vec::new(), Encoding::Engine)),
+                        2,
+                    ),
+                    None => (None, 1),
+                },
+                '{' => match rest.find('}') {
+                    Some(end) => match braced_placeholder(&rest[1..end], params)? {
+                        Some(part) => (Some(part), end + 1),
+                        None => (None, 1),
+                    },
+                    None => (None, 1),
+                },
+                _ => (None, c.len_utf8()),
+            };
+            match placeholder {
+                Some(placeholder) => {
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(placeholder);
+                }
+                None => literal.push_str(&rest[..len]),
+            }
+            rest = &rest[len..];
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// How many parts of the search term the template uses, going by the highest one it has.
+    pub fn param_count(&self) -> usize {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Placeholder(Placeholder::Param(i), ..) => Some(i + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Fills in the template: `term` for the whole search term, and `params` for its parts (empty
+    /// for parts past the end). Values are run through their filters, then `encode`d unless a
+    /// filter says to encode them differently.
+    pub fn render<F>(&self, term: &str, params: &[String], encode: F) -> String
+    where
+        F: Fn(&str) -> String,
+    {
+        self.parts
+            .iter()
+            .map(|part| {
+                let (placeholder, filters, encoding) = match part {
+                    Part::Literal(s) => return s.clone(),
+                    Part::Placeholder(placeholder, filters, encoding) => {
+                        (placeholder, filters, encoding)
+                    }
+                };
+                let mut value = match placeholder {
+                    Placeholder::Term => term.to_string(),
+                    Placeholder::Param(i) => params.get(*i).cloned().unwrap_or_default(),
+                };
+                for filter in filters {
+                    value = match filter {
+                        Filter::Lower => value.to_lowercase(),
+                        Filter::Upper => value.to_uppercase(),
+                        Filter::Replace(from, to) => value.replace(from.as_str(), to),
+                        Filter::Base64 => BASE64.encode(&value),
+                    };
+                }
+                match encoding {
+                    Encoding::Engine => encode(&value),
+                    Encoding::Raw => value,
+                    Encoding::Path => {
+                        utf8_percent_encode(&value, PATH_SEGMENT_ENCODE_SET).to_string()
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// `%s` or `%1` to `%9` at the start of `s`.
+fn percent_placeholder(s: &str) -> Option<Placeholder> {
+    let mut chars = s.chars().skip(1);
+    match chars.next()? {
+        's' => Some(Placeholder::Term),
+        c @ '1'..='9' if !chars.next().is_some_and(|x| x.is_ascii_hexdigit()) => {
+            Some(Placeholder::Param(c as usize - '1' as usize))
+        }
+        _ => None,
+    }
+}
+
+/// What's between the braces of `{name|filter|...}`, or `None` if it doesn't look like a
+/// placeholder and so is left in the URL as it is.
+fn braced_placeholder(inner: &str, params: &[String]) -> Result<Option<Part>, String> {
+    let mut pieces = inner.split('|');
+    let name = pieces.next().unwrap_or_default().trim();
+    if !is_placeholder_name(name) {
+        return Ok(None);
+    }
+    let placeholder = named_placeholder(name, params)?;
+    let mut filters = Vec::new();
+    let mut encoding = Encoding::Engine;
+    for piece in pieces {
+        let (filter, arg) = match piece.find(':') {
+            Some(i) => (piece[..i].trim(), Some(&piece[i + 1..])),
+            None => (piece.trim(), None),
+        };
+        match (filter, arg) {
+            ("lower", None) => filters.push(Filter::Lower),
+            ("upper", None) => filters.push(Filter::Upper),
+            ("base64", None) => filters.push(Filter::Base64),
+            ("replace", Some(arg)) => match arg.find(',') {
+                Some(i) => filters.push(Filter::Replace(
+                    arg[..i].to_string(),
+                    arg[i + 1..].to_string(),
+                )),
+                None => return Err(format!("Expected `replace:FROM,TO` in {{{}}}", inner)),
+            },
+            ("raw", None) | ("path", None) => {
+                if encoding != Encoding::Engine {
+                    let e = format!("Only one of `raw` and `path` can be used in {{{}}}", inner);
+                    return Err(e);
+                }
+                encoding = if filter == "raw" {
+                    Encoding::Raw
+                } else {
+                    Encoding::Path
+                };
+            }
+            _ => return Err(format!("Unknown filter `{}` in {{{}}}", piece, inner)),
+        }
+    }
+    Ok(Some(Part::Placeholder(placeholder, filters, encoding)))
+}
+
+/// Whether `{name}` is meant as a placeholder, rather than a literal brace in the URL.
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn named_placeholder(name: &str, params: &[String]) -> Result<Placeholder, String> {
+    if let Ok(n) = name.parse::<usize>() {
+        if n == 0 {
+            return Err("Parameters are numbered from 1, so {0} isn't one".into());
+        }
+        return Ok(Placeholder::Param(n - 1));
+    }
+    match params.iter().position(|x| x == name) {
+        Some(i) => Ok(Placeholder::Param(i)),
+        None if name == "query" => Ok(Placeholder::Term),
+        None => Err(format!(
+            "Unknown parameter {{{}}}, it has to be listed in `params`",
+            name
+        )),
+    }
+}
+
+/// Splits `term` into `count` parts at `separator` (or whitespace if there's none), with the last
+/// part getting the rest of the term. There can be fewer parts if the term runs out.
+pub fn split_params(term: &str, separator: Option<&str>, count: usize) -> Vec<String> {
+    match separator {
+        Some(separator) if !separator.is_empty() => term
+            .splitn(count, separator)
+            .map(|x| x.trim().to_string())
+            .collect(),
+        _ => {
+            let mut parts = Vec::new();
+            let mut rest = term.trim();
+            while !rest.is_empty() && parts.len() + 1 < count {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                parts.push(rest[..end].to_string());
+                rest = rest[end..].trim_start();
+            }
+            if !rest.is_empty() && count > 0 {
+                parts.push(rest.to_string());
+            }
+            parts
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, params: &[&str], term: &str, count: usize) -> String {
+        let params: Vec<String> = params.iter().map(|x| x.to_string()).collect();
+        let template = Template::parse(template, &params).unwrap();
+        let values = split_params(term, None, count.max(params.len()));
+        template.render(term, &values, |x| x.replace(' ', "+"))
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            render(
+                "https://example.com/%1/issues?q=%2",
+                &[],
+                "rust-lang/rust borrow checker",
+                2
+            ),
+            "https://example.com/rust-lang/rust/issues?q=borrow+checker"
+        );
+        assert_eq!(
+            render(
+                "https://gh.com/{repo}/issues?q={query}",
+                &["repo", "query"],
+                "a/b  x y",
+                0
+            ),
+            "https://gh.com/a/b/issues?q=x+y"
+        );
+        assert_eq!(
+            render("/?q={query}&all=%s", &[], "x y", 0),
+            "/?q=x+y&all=x+y"
+        );
+        assert_eq!(render("/{2}/{1}", &[], "en de word", 2), "/de+word/en");
+        // missing parts are empty
+        assert_eq!(render("/%1/%2", &[], "en", 2), "/en/");
+    }
+
+    #[test]
+    fn literals_are_kept() {
+        let template = Template::parse("/a%20b/%2F{x-y}{}%", &[]).unwrap();
+        assert_eq!(template.param_count(), 0);
+        assert_eq!(
+            template.render("t", &[], str::to_string),
+            "/a%20b/%2F{x-y}{}%"
+        );
+        let template = Template::parse("/%1%2F", &[]).unwrap();
+        assert_eq!(template.param_count(), 1);
+        assert_eq!(template.render("t", &["a".into()], str::to_string), "/a%2F");
+    }
+
+    #[test]
+    fn bad_placeholders() {
+        assert!(Template::parse("/{repo}", &[]).is_err());
+        assert!(Template::parse("/{0}", &[]).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let render = |template: &str| {
+            let template = Template::parse(template, &[]).unwrap();
+            template.render("Rust Lang/Book", &[], |x| x.replace(' ', "+"))
+        };
+        assert_eq!(render("{query|lower}"), "rust+lang/book");
+        assert_eq!(render("{ query | upper | raw }"), "RUST LANG/BOOK");
+        assert_eq!(render("{query|path}"), "Rust%20Lang%2FBook");
+        assert_eq!(render("{query|lower|replace: ,-|path}"), "rust-lang%2Fbook");
+        assert_eq!(render("{query|base64|raw}"), "UnVzdCBMYW5nL0Jvb2s=");
+        assert!(Template::parse("{query|nope}", &[]).is_err());
+        assert!(Template::parse("{query|replace:x}", &[]).is_err());
+        assert!(Template::parse("{query|raw|path}", &[]).is_err());
+    }
+
+    #[test]
+    fn separators() {
+        assert_eq!(
+            split_params("en:de:a:b", Some(":"), 3),
+            vec!["en", "de", "a:b"]
+        );
+        assert_eq!(split_params("en , de", Some(","), 3), vec!["en", "de"]);
+        assert_eq!(split_params(" a  b c ", None, 2), vec!["a", "b c"]);
+        assert_eq!(split_params("a b", None, 1), vec!["a b"]);
+        assert!(split_params("a b", None, 0).is_empty());
+    }
+
+    #[test]
+    fn engine_params() {
+        let config = crate::parse_config(
+            r#"
+            [engines._default]
+            name = "Google"
+            search_url = "https://www.google.com/search?q=%s"
+
+            [engines.tr]
+            name = "Translate"
+            search_url = "https://translate.google.com/?sl={from}&tl={to}&text={text}"
+            params = ["from", "to", "text"]
+            param_separator = ":"
+            param_defaults = { from = "auto", to = "en" }
+
+            [engines.gh]
+            name = "GitHub issues"
+            search_url = "https://github.com/%1/issues?q=%2"
+            param_defaults = { 1 = "efskap/sefr" }
+
+            [keybinds]
+            "#,
+        )
+        .unwrap();
+        let tr = &config.engines["tr"];
+        assert_eq!(
+            tr.format_search_url("de:fr:guten tag"),
+            "https://translate.google.com/?sl=de&tl=fr&text=guten%20tag"
+        );
+        assert_eq!(
+            tr.format_search_url("::hola"),
+            "https://translate.google.com/?sl=auto&tl=en&text=hola"
+        );
+        // parsed once when loading the config, but engines built some other way work too
+        assert!(tr.parsed_templates.contains_key(&tr.search_url));
+        let mut unparsed = tr.clone();
+        unparsed.parsed_templates.clear();
+        assert_eq!(
+            unparsed.format_search_url("de:fr:guten tag"),
+            tr.format_search_url("de:fr:guten tag")
+        );
+        let gh = &config.engines["gh"];
+        assert_eq!(
+            gh.format_search_url(""),
+            "https://github.com/efskap/sefr/issues?q="
+        );
+
+        // pluses and percent signs typed into the search term are kept apart from what spaces
+        // become
+        let google = &config.engines[""];
+        assert_eq!(
+            google.format_search_url("50% of c++"),
+            "https://www.google.com/search?q=50%25%20of%20c%2B%2B"
+        );
+        let mut plus = google.clone();
+        plus.space_becomes = "+".into();
+        assert_eq!(
+            plus.format_search_url("a&b c++"),
+            "https://www.google.com/search?q=a%26b+c%2B%2B"
+        );
+
+        let bad = "[engines._default]\nname = \"x\"\nsearch_url = \"/{nope}\"\n[keybinds]\n";
+        assert!(crate::parse_config(bad).is_err());
+    }
+}
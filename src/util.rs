@@ -17,18 +17,101 @@
 
 use crate::*;
 
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Fits `s` into `n` chars by cutting off the start, which is replaced by dots.
 pub fn truncate_from_end(s: &str, n: usize) -> String {
-    if s.len() <= n {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= n {
         s.into()
     } else {
-        let delta = s.len() - n; // how many characters don't fit
-        let truncd = &s[delta..];
+        let delta = chars.len() - n; // how many characters don't fit
+        let truncd = &chars[delta..];
         let non_dots = &truncd[min(3, truncd.len())..]; // part of string that doesn't get turned into dots
-        let with_dots = format!("...{}", non_dots);
-        with_dots[with_dots.len().checked_sub(n).unwrap_or(0)..].into()
+        let with_dots = format!("...{}", non_dots.iter().collect::<String>());
+        with_dots
+            .chars()
+            .skip((3 + non_dots.len()).saturating_sub(n))
+            .collect()
+    }
+}
+
+/// Fits `s` into `n` chars by cutting off the end, which is replaced by dots.
+pub fn truncate_end(s: &str, n: usize) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= n {
+        s.into()
+    } else {
+        let kept: String = chars[..n.saturating_sub(3)].iter().collect();
+        format!("{}{}", kept, &"..."[..min(3, n)])
+    }
+}
+
+/// Fits `s` into `n` columns while keeping the char at `cursor` visible, cutting off the start
+/// (and the end, if the cursor is far from it) with dots. Returns the visible text and the
+/// cursor's column in it.
+pub fn truncate_around_cursor(s: &str, cursor: usize, n: usize) -> (String, usize) {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= n {
+        return (s.into(), cursor);
+    }
+    // show as much of the end as possible, but scroll back so the cursor isn't under the dots
+    let start = min(chars.len() - n, cursor.saturating_sub(3));
+    let end = start + n;
+    let mut visible: String = chars[start..end].iter().collect();
+    if start > 0 {
+        visible = format!(
+            "...{}",
+            chars[min(start + 3, end)..end].iter().collect::<String>()
+        );
+    }
+    if end < chars.len() {
+        let keep: String = visible.chars().take(n.saturating_sub(3)).collect();
+        visible = format!("{}...", keep);
+    }
+    let visible: String = visible.chars().take(n).collect();
+    (visible, cursor - start)
+}
+
+/// Replaces a leading `~` with the home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            match directories::BaseDirs::new() {
+                Some(dirs) => dirs.home_dir().join(rest.trim_start_matches('/')),
+                None => PathBuf::from(path),
+            }
+        }
+        _ => PathBuf::from(path),
     }
 }
 
+/// Writes `contents` to `path`, creating its directory if needed. The file is replaced in one
+/// go, so that a crash or another sefr saving at the same time can't leave it half-written.
+pub fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+/// The current Unix timestamp in seconds.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
 pub fn is_default<T: Default + PartialEq>(t: &T) -> bool {
     t == &T::default()
 }
@@ -53,7 +136,59 @@ mod tests {
         assert_eq!(truncate_from_end("abcd", 3), "...");
         assert_eq!(truncate_from_end("abcde", 4), "...e");
         assert_eq!(truncate_from_end("the quick brown", 8), "...brown");
+        assert_eq!(truncate_from_end("Wikipédia: erreur", 8), "...rreur");
+        assert_eq!(truncate_from_end("/tmp/höhle/ärger.txt", 10), "...ger.txt");
+        assert_eq!(truncate_from_end("日本語", 2), "..");
+    }
 
+    #[test]
+    fn trunc_end() {
+        assert_eq!(truncate_end("abcd", 4), "abcd");
+        assert_eq!(truncate_end("abcd", 2), "..");
+        assert_eq!(truncate_end("the quick brown", 8), "the q...");
+        assert_eq!(
+            truncate_end("Wikipédia, l'encyclopédie", 12),
+            "Wikipédia..."
+        );
     }
-}
 
+    #[test]
+    fn trunc_around_cursor() {
+        assert_eq!(truncate_around_cursor("abcd", 2, 4), ("abcd".into(), 2));
+        assert_eq!(
+            truncate_around_cursor("the quick brown", 15, 8),
+            ("...brown".into(), 8)
+        );
+        assert_eq!(
+            truncate_around_cursor("the quick brown", 4, 8),
+            ("...qu...".into(), 3)
+        );
+        assert_eq!(
+            truncate_around_cursor("the quick brown", 0, 8),
+            ("the q...".into(), 0)
+        );
+    }
+
+    #[test]
+    fn atomic_write() {
+        let dir = std::env::temp_dir().join(format!("sefr-write-{}", std::process::id()));
+        let path = dir.join("file.txt");
+        write_atomically(&path, "one").unwrap();
+        write_atomically(&path, "two").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "two");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tilde() {
+        assert_eq!(expand_tilde("/etc/hosts"), PathBuf::from("/etc/hosts"));
+        assert_eq!(expand_tilde("~user/x"), PathBuf::from("~user/x"));
+        let home = directories::BaseDirs::new()
+            .unwrap()
+            .home_dir()
+            .to_path_buf();
+        assert_eq!(expand_tilde("~/a/b.txt"), home.join("a/b.txt"));
+        assert_eq!(expand_tilde("~"), home);
+    }
+}
@@ -0,0 +1,139 @@
+// *************************************************************************
+// * Copyright (C) 2019 Dmitry Narkevich (me@dmitry.lol)                   *
+// *                                                                       *
+// * This program is free software: you can redistribute it and/or modify  *
+// * it under the terms of the GNU General Public License as published by  *
+// * the Free Software Foundation, either version 3 of the License, or     *
+// * (at your option) any later version.                                   *
+// *                                                                       *
+// * This program is distributed in the hope that it will be useful,       *
+// * but WITHOUT ANY WARRANTY; without even the implied warranty of        *
+// * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the         *
+// * GNU General Public License for more details.                          *
+// *                                                                       *
+// * You should have received a copy of the GNU General Public License     *
+// * along with this program.  If not, see <http://www.gnu.org/licenses/>. *
+// *************************************************************************
+
+use roxmltree::{Document, Node};
+use std::fmt;
+use std::str::FromStr;
+
+/// Slash-separated element names from the root element down, optionally ending in `@attribute`,
+/// e.g. `toplevel/CompleteSuggestion/suggestion@data`. `*` matches any element, and namespaces
+/// are ignored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlPath {
+    elements: Vec<String>,
+    attribute: Option<String>,
+}
+
+impl XmlPath {
+    /// Finds the elements the path leads to in `doc`, in document order.
+    ///
+    /// If a step leads nowhere, returns the part of the path up to and including that step as
+    /// the error.
+    pub fn select<'a, 'input>(
+        &self,
+        doc: &'a Document<'input>,
+    ) -> Result<Vec<Node<'a, 'input>>, String> {
+        let matches_name = |node: &Node, name: &str| {
+            node.is_element() && (name == "*" || node.tag_name().name() == name)
+        };
+        let mut nodes = vec![doc.root_element()];
+        for (depth, name) in self.elements.iter().enumerate() {
+            if depth == 0 {
+                nodes.retain(|x| matches_name(x, name));
+            } else {
+                nodes = nodes
+                    .iter()
+                    .flat_map(|x| x.children())
+                    .filter(|x| matches_name(x, name))
+                    .collect();
+            }
+            if nodes.is_empty() {
+                return Err(self.elements[..=depth].join("/"));
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// The path's attribute of `node`, or all of the text inside it if the path has none.
+    pub fn value(&self, node: &Node) -> Option<String> {
+        match self.attribute {
+            Some(ref attribute) => node.attribute(attribute.as_str()).map(str::to_string),
+            None => Some(
+                node.descendants()
+                    .filter(|x| x.is_text())
+                    .filter_map(|x| x.text())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for XmlPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.elements.join("/"))?;
+        if let Some(ref attribute) = self.attribute {
+            write!(f, "@{}", attribute)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for XmlPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (elements, attribute) = match s.find('@') {
+            Some(i) => (&s[..i], Some(s[i + 1..].to_string())),
+            None => (s, None),
+        };
+        let elements: Vec<String> = elements
+            .trim_start_matches('/')
+            .split('/')
+            .map(str::to_string)
+            .collect();
+        if elements.iter().any(String::is_empty) || attribute.as_deref() == Some("") {
+            return Err(format!("Invalid XML path '{}'", s));
+        }
+        Ok(XmlPath {
+            elements,
+            attribute,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(path: &str, body: &str) -> Result<Vec<Option<String>>, String> {
+        let path: XmlPath = path.parse()?;
+        let doc = Document::parse(body).unwrap();
+        Ok(path.select(&doc)?.iter().map(|x| path.value(x)).collect())
+    }
+
+    #[test]
+    fn parse() {
+        assert!("a//b".parse::<XmlPath>().is_err());
+        assert!("a/b@".parse::<XmlPath>().is_err());
+        assert!("@c".parse::<XmlPath>().is_err());
+        assert_eq!("/a/b@c".parse::<XmlPath>().unwrap().to_string(), "a/b@c");
+        assert_eq!("a/*".parse::<XmlPath>().unwrap().to_string(), "a/*");
+    }
+
+    #[test]
+    fn elements_and_attributes() {
+        let body = r#"<a xmlns="urn:x"><b c="1">one</b><d><b>two</b></d><b>th<i>ree</i></b></a>"#;
+        assert_eq!(
+            select("a/b", body),
+            Ok(vec![Some("one".into()), Some("three".into())])
+        );
+        assert_eq!(select("a/b@c", body), Ok(vec![Some("1".into()), None]));
+        assert_eq!(select("a/*/b", body), Ok(vec![Some("two".into())]));
+        assert_eq!(select("a/nope/b", body), Err("a/nope".into()));
+        assert_eq!(select("b", body), Err("b".into()));
+    }
+}